@@ -0,0 +1,82 @@
+//! Picks a scale per frame so every frame has about the same number of dots.
+//!
+//! Dot counts grow with the scale, so each frame is searched on its own and the scales are then
+//! smoothed in frame order so the image does not pump between frames.
+
+use crate::{render::Dots, settings::AdaptiveSettings};
+
+/// Renders tried per frame before settling for the closest
+const SEARCH_STEPS: usize = 8;
+
+/// Scale within `settings` whose dots land in the target range, or the closest one tried.
+///
+/// `render` draws the frame at a scale and `count_dots` tells how many dots that draws, the search
+/// starts at `start`.
+pub fn search(
+    settings: &AdaptiveSettings,
+    start: f64,
+    mut render: impl FnMut(f64) -> Dots,
+    count_dots: impl Fn(&Dots) -> usize,
+) -> (f64, Dots) {
+    // searched in log space, dot counts follow the area so halving and doubling are alike
+    let (mut low, mut high) = (settings.min_scale.ln(), settings.max_scale.ln());
+    let mut scale = start.clamp(settings.min_scale, settings.max_scale);
+    let mut best: Option<(usize, f64, Dots)> = None;
+
+    for _ in 0..SEARCH_STEPS {
+        let dots = render(scale);
+        let count = count_dots(&dots);
+
+        let miss = if count < settings.min_dots {
+            low = scale.ln();
+            settings.min_dots - count
+        } else if count > settings.max_dots {
+            high = scale.ln();
+            count - settings.max_dots
+        } else {
+            return (scale, dots);
+        };
+
+        if best
+            .as_ref()
+            .is_none_or(|(best_miss, ..)| miss < *best_miss)
+        {
+            best = Some((miss, scale, dots));
+        }
+
+        scale = ((low + high) / 2.).exp();
+    }
+
+    let (_, scale, dots) = best.expect("searched at least once");
+    (scale, dots)
+}
+
+/// Limits how fast the scale changes from one frame to the next.
+#[derive(Debug, Clone)]
+pub struct Smoothing {
+    max_change: f64,
+    previous: Option<f64>,
+}
+
+impl Smoothing {
+    pub fn new(settings: &AdaptiveSettings) -> Self {
+        Self {
+            max_change: settings.max_change,
+            previous: None,
+        }
+    }
+
+    /// Scale of the next frame given the one its search found.
+    pub fn next(&mut self, ideal: f64) -> f64 {
+        let res = match self.previous {
+            Some(previous) => ideal.clamp(
+                previous / (1. + self.max_change),
+                previous * (1. + self.max_change),
+            ),
+            None => ideal,
+        };
+
+        self.previous = Some(res);
+        res
+    }
+}
@@ -0,0 +1,141 @@
+//! Solves projection settings from where the image should show up in game, and draws a test
+//! grid to check them.
+
+use crate::{
+    compress::compress,
+    emit::{Emitter, HltasEmitter},
+    hltas::Hltas,
+    projection::dots_to_views,
+    render::Dots,
+    settings::{ProjectionKind, ProjectionSettings, Settings},
+    timing::Quantization,
+};
+
+/// Where the image should be, angles are `[pitch, yaw]`.
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    /// Linear projection spanning these corners of the image
+    Corners {
+        top_left: [f64; 2],
+        bottom_right: [f64; 2],
+    },
+    /// Perspective projection filling the screen around this angle
+    Center { center: [f64; 2], fov: f64 },
+}
+
+/// Projection putting the image on `target`, everything `target` does not decide comes from
+/// `base`.
+///
+/// Linear projection has one angle per pixel, so when the corners are not the same shape as
+/// the screen the screen height is changed to match.
+pub fn solve(target: Target, base: &ProjectionSettings) -> Result<ProjectionSettings, String> {
+    match target {
+        Target::Corners {
+            top_left,
+            bottom_right,
+        } => {
+            // yaw wraps around
+            let yaw_span = (bottom_right[1] - top_left[1] + 180.).rem_euclid(360.) - 180.;
+            let pitch_span = top_left[0] - bottom_right[0];
+
+            // image x goes towards bigger yaw and image y towards smaller pitch
+            if yaw_span <= 0. || pitch_span <= 0. {
+                return Err(format!(
+                    "top left must have a smaller yaw and a bigger pitch than bottom right, \
+                    got yaw span {yaw_span} and pitch span {pitch_span}"
+                ));
+            }
+
+            let angle_per_pixel = yaw_span / base.screen_width as f64;
+            let screen_height = (pitch_span / angle_per_pixel).round() as u32;
+
+            if screen_height == 0 {
+                return Err("corners are too flat".to_string());
+            }
+
+            Ok(ProjectionSettings {
+                kind: ProjectionKind::Linear,
+                starting_yaw: top_left[1] + yaw_span / 2.,
+                starting_pitch: bottom_right[0] + pitch_span / 2.,
+                screen_height,
+                angle_per_pixel,
+                ..base.clone()
+            })
+        }
+        Target::Center { center, fov } => {
+            if !(fov > 0. && fov < 180.) {
+                return Err(format!("fov must be between 0 and 180, got {fov}"));
+            }
+
+            Ok(ProjectionSettings {
+                kind: ProjectionKind::Perspective,
+                starting_pitch: center[0],
+                starting_yaw: center[1],
+                fov,
+                ..base.clone()
+            })
+        }
+    }
+}
+
+/// Border and `divisions` evenly spaced lines each way inside it, every `step` pixels along them.
+pub fn grid_dots(dimensions: (u32, u32), divisions: u32, step: u32) -> Dots {
+    let (width, height) = dimensions;
+    let step = step.max(1);
+    // the lines split the image into one more cell than there are lines
+    let cells = divisions + 1;
+
+    let mut res = Dots {
+        dimensions,
+        points: vec![],
+        strokes: vec![],
+    };
+
+    let columns = (0..=cells).map(|i| (i * (width - 1)) / cells);
+    let rows = (0..=cells).map(|i| (i * (height - 1)) / cells);
+
+    for x in columns {
+        res.points
+            .extend((0..height).step_by(step as usize).map(|y| [x, y]));
+    }
+
+    for y in rows {
+        res.points
+            .extend((0..width).step_by(step as usize).map(|x| [x, y]));
+    }
+
+    res.points.sort_unstable();
+    res.points.dedup();
+
+    res
+}
+
+/// Script drawing the test grid with `settings`, held on screen for `hold` seconds.
+pub fn calibration_script(settings: &Settings, divisions: u32, step: u32, hold: f64) -> Hltas {
+    let projection = &settings.projection;
+    let dimensions = (
+        ((projection.screen_width as f64 * settings.scale_factor) as u32).max(2),
+        ((projection.screen_height as f64 * settings.scale_factor) as u32).max(2),
+    );
+
+    let dots = grid_dots(dimensions, divisions, step);
+    let views = dots_to_views(&dots, projection);
+
+    let emitter = HltasEmitter::new(settings);
+    let mut body = emitter.frame(&views);
+
+    // one long frame would get clamped by the engine
+    let frametime = match Quantization::new(&settings.timing).played(settings.timing.frametime) {
+        played if played > 0. => played,
+        _ => settings.timing.frametime,
+    };
+    for _ in 0..(hold / frametime).ceil().max(1.) as u32 {
+        body.extend(emitter.delay(frametime));
+    }
+
+    if settings.output.compress {
+        body = compress(body);
+    }
+
+    emitter.script(body, None)
+}
@@ -0,0 +1,596 @@
+use std::path::PathBuf;
+
+use clap::{builder::PossibleValuesParser, Args, Parser, Subcommand, ValueEnum};
+
+use bad_apple_to_hltas::settings::{
+    AudioCommand, DotSelection, DrawOrder, FramePosition, Mode, ProjectionKind, Settings,
+    VectorSource, PRESETS,
+};
+
+/// Converts videos and images into HLTAS scripts that draw them in game by turning the view.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// TOML or JSON config file, flags override its values
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Built in preset the settings start from
+    #[arg(long, global = true, value_parser = PossibleValuesParser::new(PRESETS))]
+    pub preset: Option<String>,
+
+    /// Prints the effective settings instead of converting
+    #[arg(long, global = true, value_enum, num_args = 0..=1, default_missing_value = "toml")]
+    pub dump_config: Option<ConfigFormat>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Converts every frame of a video
+    Video {
+        /// Video to convert
+        input: PathBuf,
+
+        /// Width the video is decoded at [default: 1280]
+        #[arg(long)]
+        video_width: Option<u32>,
+
+        /// Height the video is decoded at [default: 720]
+        #[arg(long)]
+        video_height: Option<u32>,
+
+        /// Writes one script here as it is converted, `-` for stdout, instead of chained scripts
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        input_args: InputArgs,
+
+        #[command(flatten)]
+        settings: SettingsArgs,
+    },
+    /// Converts numbered image frames in number order
+    Sequence {
+        /// Folder of PNG/JPEG frames, or a pattern such as `frames/frame_*.png`
+        input: PathBuf,
+
+        /// Writes one script here as it is converted, `-` for stdout, instead of chained scripts
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        input_args: InputArgs,
+
+        #[command(flatten)]
+        settings: SettingsArgs,
+    },
+    /// Converts every frame of an animated GIF or APNG
+    Animation {
+        /// Animation to convert
+        input: PathBuf,
+
+        /// Writes one script here as it is converted, `-` for stdout, instead of chained scripts
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        input_args: InputArgs,
+
+        #[command(flatten)]
+        settings: SettingsArgs,
+    },
+    /// Parses scripts and prints a summary, malformed lines are reported with their line number
+    Check {
+        /// Scripts to check
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Also checks that the scripts are written back byte for byte
+        #[arg(long)]
+        round_trip: bool,
+    },
+    /// Plays scripts back into images, following chained scripts
+    Preview {
+        /// First script to play
+        input: PathBuf,
+
+        /// Writes every shown frame as a numbered PNG here
+        #[arg(long, required_unless_present = "gif")]
+        out_dir: Option<PathBuf>,
+
+        /// Writes every shown frame into this animated GIF
+        #[arg(long)]
+        gif: Option<PathBuf>,
+
+        /// Preview width [default: screen width]
+        #[arg(long)]
+        width: Option<u32>,
+
+        /// Preview height [default: screen height]
+        #[arg(long)]
+        height: Option<u32>,
+
+        /// Side of every dot in preview pixels
+        #[arg(long, default_value_t = 4)]
+        dot_size: u32,
+
+        /// Frames at least this long are shown
+        #[arg(long, default_value_t = 0.01)]
+        min_display_frametime: f64,
+
+        #[command(flatten)]
+        settings: SettingsArgs,
+    },
+    /// Solves projection settings from where the image should be in game, printed as a config
+    Calibrate {
+        /// Top left corner of the image as PITCH,YAW, solves a linear projection
+        #[arg(
+            long,
+            value_parser = parse_angles,
+            allow_hyphen_values = true,
+            requires = "bottom_right",
+            conflicts_with = "center"
+        )]
+        top_left: Option<[f64; 2]>,
+
+        /// Bottom right corner of the image as PITCH,YAW
+        #[arg(long, value_parser = parse_angles, allow_hyphen_values = true, requires = "top_left")]
+        bottom_right: Option<[f64; 2]>,
+
+        /// Center of the image as PITCH,YAW, solves a perspective projection with `--fov`
+        #[arg(
+            long,
+            value_parser = parse_angles,
+            allow_hyphen_values = true,
+            required_unless_present = "top_left"
+        )]
+        center: Option<[f64; 2]>,
+
+        /// Writes a script drawing a test grid with the solved projection here
+        #[arg(long)]
+        hltas: Option<PathBuf>,
+
+        /// Grid lines each way, border excluded
+        #[arg(long, default_value_t = 4)]
+        divisions: u32,
+
+        /// Image pixels between grid dots
+        #[arg(long, default_value_t = 2)]
+        step: u32,
+
+        /// Seconds the grid stays on screen
+        #[arg(long, default_value_t = 10.)]
+        hold: f64,
+
+        #[command(flatten)]
+        settings: SettingsArgs,
+    },
+    /// Converts a single image, printed to stdout
+    Image {
+        /// Image to convert
+        input: PathBuf,
+
+        /// Writes the script here instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        settings: SettingsArgs,
+    },
+}
+
+fn parse_angles(s: &str) -> Result<[f64; 2], String> {
+    let (pitch, yaw) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected PITCH,YAW, got `{s}`"))?;
+
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("invalid angle `{value}`"))
+    };
+
+    Ok([parse(pitch)?, parse(yaw)?])
+}
+
+/// Overrides for [`InputSettings`](bad_apple_to_hltas::settings::InputSettings).
+#[derive(Debug, Args)]
+pub struct InputArgs {
+    /// Frame rate of the input, sets the delay frametime [default: from the input, else keep frametime]
+    #[arg(long, help_heading = "Input")]
+    fps: Option<f64>,
+
+    /// First frame converted, a frame index or a timestamp like `12.5s` or `1:02.5`
+    #[arg(long, help_heading = "Input")]
+    start: Option<FramePosition>,
+
+    /// Frame the conversion stops before, a frame index or a timestamp
+    #[arg(long, help_heading = "Input")]
+    end: Option<FramePosition>,
+
+    /// Takes every nth frame, the delay frametime grows to match [default: 1]
+    #[arg(long, help_heading = "Input")]
+    stride: Option<usize>,
+
+    /// Drops or repeats frames to get this frame rate, sets the delay frametime
+    #[arg(long, help_heading = "Input")]
+    output_fps: Option<f64>,
+
+    /// Stops after this many output frames
+    #[arg(long, help_heading = "Input")]
+    max_frames: Option<usize>,
+}
+
+impl InputArgs {
+    pub fn apply(&self, settings: &mut Settings) {
+        let input = &mut settings.input;
+
+        if self.fps.is_some() {
+            input.fps = self.fps;
+        }
+        if self.start.is_some() {
+            input.start = self.start;
+        }
+        if self.end.is_some() {
+            input.end = self.end;
+        }
+        if let Some(stride) = self.stride {
+            input.stride = stride;
+        }
+        if self.output_fps.is_some() {
+            input.output_fps = self.output_fps;
+        }
+        if self.max_frames.is_some() {
+            settings.max_frames = self.max_frames;
+        }
+    }
+}
+
+/// Overrides for [`Settings`], unset flags keep the default.
+#[derive(Debug, Args)]
+pub struct SettingsArgs {
+    /// Scale applied to the image before processing [default: 0.125]
+    #[arg(long, help_heading = "Image")]
+    scale_factor: Option<f64>,
+
+    /// How the image is turned into dots [default: dithering]
+    #[arg(long, value_enum, help_heading = "Image")]
+    mode: Option<Mode>,
+
+    /// Picks the scale per frame to land every frame in a dot range [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Adaptive")]
+    adaptive: Option<bool>,
+
+    /// Fewest dots an adaptive frame aims for [default: 150]
+    #[arg(long, help_heading = "Adaptive")]
+    target_min_dots: Option<usize>,
+
+    /// Most dots an adaptive frame aims for [default: 300]
+    #[arg(long, help_heading = "Adaptive")]
+    target_max_dots: Option<usize>,
+
+    /// Smallest adaptive scale [default: 0.02]
+    #[arg(long, help_heading = "Adaptive")]
+    min_scale: Option<f64>,
+
+    /// Biggest adaptive scale [default: 0.5]
+    #[arg(long, help_heading = "Adaptive")]
+    max_scale: Option<f64>,
+
+    /// How much the adaptive scale may change between frames, 0.1 is 10% [default: 0.1]
+    #[arg(long, help_heading = "Adaptive")]
+    max_scale_change: Option<f64>,
+
+    /// Canny gaussian blur sigma [default: 1.2]
+    #[arg(long, help_heading = "Canny")]
+    sigma: Option<f64>,
+
+    /// Canny strong threshold [default: 0.2]
+    #[arg(long, help_heading = "Canny")]
+    strong_threshold: Option<f64>,
+
+    /// Canny weak threshold [default: 0.01]
+    #[arg(long, help_heading = "Canny")]
+    weak_threshold: Option<f64>,
+
+    /// Pixels the vector mode traces [default: canny-edge]
+    #[arg(long, value_enum, help_heading = "Vector")]
+    vector_source: Option<VectorSource>,
+
+    /// Farthest in pixels a traced outline may stray from the lines drawing it [default: 1]
+    #[arg(long, help_heading = "Vector")]
+    tolerance: Option<f64>,
+
+    /// Outlines with fewer pixels are dropped [default: 3]
+    #[arg(long, help_heading = "Vector")]
+    min_stroke_length: Option<usize>,
+
+    /// Most degrees the view moves between two frames of a line, never under one image pixel
+    /// [default: one image pixel]
+    #[arg(long, help_heading = "Vector")]
+    stroke_step: Option<f64>,
+
+    /// How image coordinates become view angles [default: linear]
+    #[arg(long, value_enum, help_heading = "Projection")]
+    projection: Option<ProjectionKind>,
+
+    /// Yaw at the center of the image [default: 90.197754]
+    #[arg(long, allow_hyphen_values = true, help_heading = "Projection")]
+    starting_yaw: Option<f64>,
+
+    /// Pitch at the center of the image [default: -0.022]
+    #[arg(long, allow_hyphen_values = true, help_heading = "Projection")]
+    starting_pitch: Option<f64>,
+
+    /// Linear: width the image is stretched to. Perspective: game resolution [default: 1280]
+    #[arg(long, help_heading = "Projection")]
+    screen_width: Option<u32>,
+
+    /// Linear: height the image is stretched to. Perspective: game resolution [default: 720]
+    #[arg(long, help_heading = "Projection")]
+    screen_height: Option<u32>,
+
+    /// Linear: degrees per screen pixel [default: 0.03125]
+    #[arg(long, help_heading = "Projection")]
+    angle_per_pixel: Option<f64>,
+
+    /// Perspective: horizontal fov as given to `default_fov` [default: 90]
+    #[arg(long, help_heading = "Projection")]
+    fov: Option<f64>,
+
+    /// Perspective: aspect ratio the game renders at [default: screen width / screen height]
+    #[arg(long, help_heading = "Projection")]
+    aspect_ratio: Option<f64>,
+
+    /// Perspective: widens the fov past 4:3 like GoldSrc on widescreen [default: true]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Projection")]
+    fov_4_3_scaling: Option<bool>,
+
+    /// Frametime used for 0ms frames [default: 0.0000000001]
+    #[arg(long, help_heading = "Timing")]
+    zero_ms_frametime: Option<f64>,
+
+    /// Frametime of the delay frame ending every video frame [default: 0.04171]
+    #[arg(long, help_heading = "Timing")]
+    frametime: Option<f64>,
+
+    /// Sizes every delay so frame N ends at N * frametime, drawing time included [default: true]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Timing")]
+    drift_free: Option<bool>,
+
+    /// Rounds frametimes to whole milliseconds like the engine does [default: true]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Timing")]
+    quantize: Option<bool>,
+
+    /// Waits after every dot [default: true]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Timing")]
+    slow_draw: Option<bool>,
+
+    /// Frametime of the wait after every dot [default: 0.000001]
+    #[arg(long, help_heading = "Timing")]
+    slow_wait: Option<f64>,
+
+    /// Caps dot count on screen [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Dots")]
+    count_dots: Option<bool>,
+
+    /// Dot cap when counting dots [default: 240]
+    #[arg(long, help_heading = "Dots")]
+    max_dots: Option<usize>,
+
+    /// Which dots are kept when capping [default: uniform]
+    #[arg(long, value_enum, help_heading = "Dots")]
+    dot_selection: Option<DotSelection>,
+
+    /// Order the dots of a frame are drawn in [default: scan]
+    #[arg(long, value_enum, help_heading = "Dots")]
+    draw_order: Option<DrawOrder>,
+
+    /// Most degrees the view turns in one frame, bigger turns go through hidden views
+    #[arg(long, help_heading = "Dots")]
+    max_angle_step: Option<f64>,
+
+    /// Draws only the dots a frame adds, clearing the screen now and then [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Delta")]
+    delta: Option<bool>,
+
+    /// Share of left over dots a frame tolerates before it is redrawn whole [default: 0.2]
+    #[arg(long, help_heading = "Delta")]
+    max_stale: Option<f64>,
+
+    /// Most frames between whole redraws [default: 24]
+    #[arg(long, help_heading = "Delta")]
+    keyframe_interval: Option<usize>,
+
+    /// Keeps a frame on screen instead of redrawing it when the next frames repeat it [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Collapse")]
+    collapse: Option<bool>,
+
+    /// Share of dots a frame may add or drop and still count as a repeat [default: 0]
+    #[arg(long, help_heading = "Collapse")]
+    collapse_threshold: Option<f64>,
+
+    /// Song started by the script, such as `media/bad_apple.mp3`
+    #[arg(long, help_heading = "Audio")]
+    audio: Option<String>,
+
+    /// Console command playing the song [default: mp3]
+    #[arg(long, value_enum, help_heading = "Audio")]
+    audio_command: Option<AudioCommand>,
+
+    /// Output frame the song starts with, a frame index or a timestamp [default: 0]
+    #[arg(long, help_heading = "Audio")]
+    audio_start: Option<FramePosition>,
+
+    /// Echoes where the song should be at the start of every chained script [default: true]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Audio")]
+    audio_resync: Option<bool>,
+
+    /// Starts capturing with the first frame and stops after the last [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Capture")]
+    capture: Option<bool>,
+
+    /// Sets `bxt_cap_fps` before capturing [default: bxt's]
+    #[arg(long, help_heading = "Capture")]
+    capture_fps: Option<f64>,
+
+    /// Console command starting the capture [default: bxt_cap_start]
+    #[arg(long, help_heading = "Capture")]
+    capture_start_command: Option<String>,
+
+    /// Console command stopping the capture [default: bxt_cap_stop]
+    #[arg(long, help_heading = "Capture")]
+    capture_stop_command: Option<String>,
+
+    /// Chained scripts in a folder instead of one script on stdout [default: true]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Output")]
+    separate_hltas: Option<bool>,
+
+    /// Folder chained scripts are written to [default: `out` next to the input]
+    #[arg(long, help_heading = "Output")]
+    output_dir: Option<PathBuf>,
+
+    /// Chained scripts are named `<PREFIX><number>.hltas` [default: none]
+    #[arg(long, help_heading = "Output")]
+    prefix: Option<String>,
+
+    /// Zero pads script numbers to this many digits [default: 0]
+    #[arg(long, help_heading = "Output")]
+    padding: Option<usize>,
+
+    /// Folder `bxt_tas_loadscript` loads chained scripts from, relative to the mod folder [default: out]
+    #[arg(long, help_heading = "Output")]
+    game_dir: Option<String>,
+
+    /// Video frames per chained script [default: 1]
+    #[arg(long, help_heading = "Output")]
+    chunk_frames: Option<usize>,
+
+    /// Starts a new chained script before one gets more lines than this
+    #[arg(long, help_heading = "Output")]
+    max_lines: Option<usize>,
+
+    /// Starts a new chained script before one gets bigger than this many bytes
+    #[arg(long, help_heading = "Output")]
+    max_bytes: Option<usize>,
+
+    /// Writes runs of the same frame as one line with a repeat count [default: true]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Output")]
+    compress: Option<bool>,
+}
+
+impl SettingsArgs {
+    pub fn apply(&self, settings: &mut Settings) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+
+        set(&mut settings.scale_factor, &self.scale_factor);
+        set(&mut settings.mode, &self.mode);
+
+        let adaptive = &mut settings.adaptive;
+        set(&mut adaptive.enabled, &self.adaptive);
+        set(&mut adaptive.min_dots, &self.target_min_dots);
+        set(&mut adaptive.max_dots, &self.target_max_dots);
+        set(&mut adaptive.min_scale, &self.min_scale);
+        set(&mut adaptive.max_scale, &self.max_scale);
+        set(&mut adaptive.max_change, &self.max_scale_change);
+
+        set(&mut settings.canny.sigma, &self.sigma);
+        set(&mut settings.canny.strong_threshold, &self.strong_threshold);
+        set(&mut settings.canny.weak_threshold, &self.weak_threshold);
+
+        let vector = &mut settings.vector;
+        set(&mut vector.source, &self.vector_source);
+        set(&mut vector.tolerance, &self.tolerance);
+        set(&mut vector.min_length, &self.min_stroke_length);
+        if self.stroke_step.is_some() {
+            vector.stroke_step = self.stroke_step;
+        }
+
+        let projection = &mut settings.projection;
+        set(&mut projection.kind, &self.projection);
+        set(&mut projection.starting_yaw, &self.starting_yaw);
+        set(&mut projection.starting_pitch, &self.starting_pitch);
+        set(&mut projection.screen_width, &self.screen_width);
+        set(&mut projection.screen_height, &self.screen_height);
+        set(&mut projection.angle_per_pixel, &self.angle_per_pixel);
+        set(&mut projection.fov, &self.fov);
+        if self.aspect_ratio.is_some() {
+            projection.aspect_ratio = self.aspect_ratio;
+        }
+        set(&mut projection.fov_4_3_scaling, &self.fov_4_3_scaling);
+
+        let timing = &mut settings.timing;
+        set(&mut timing.zero_ms_frametime, &self.zero_ms_frametime);
+        set(&mut timing.frametime, &self.frametime);
+        set(&mut timing.drift_free, &self.drift_free);
+        set(&mut timing.quantize, &self.quantize);
+        set(&mut timing.slow_draw, &self.slow_draw);
+        set(&mut timing.slow_wait, &self.slow_wait);
+
+        set(&mut settings.count_dots, &self.count_dots);
+        set(&mut settings.max_dots, &self.max_dots);
+        set(&mut settings.dot_selection, &self.dot_selection);
+        set(&mut settings.draw_order, &self.draw_order);
+        if self.max_angle_step.is_some() {
+            settings.max_angle_step = self.max_angle_step;
+        }
+
+        let delta = &mut settings.delta;
+        set(&mut delta.enabled, &self.delta);
+        set(&mut delta.max_stale, &self.max_stale);
+        set(&mut delta.keyframe_interval, &self.keyframe_interval);
+
+        set(&mut settings.collapse.enabled, &self.collapse);
+        set(
+            &mut settings.collapse.max_difference,
+            &self.collapse_threshold,
+        );
+
+        let audio = &mut settings.audio;
+        if self.audio.is_some() {
+            audio.path = self.audio.clone();
+        }
+        set(&mut audio.command, &self.audio_command);
+        set(&mut audio.start, &self.audio_start);
+        set(&mut audio.resync, &self.audio_resync);
+
+        let capture = &mut settings.capture;
+        set(&mut capture.enabled, &self.capture);
+        if self.capture_fps.is_some() {
+            capture.fps = self.capture_fps;
+        }
+        set(&mut capture.start_command, &self.capture_start_command);
+        set(&mut capture.stop_command, &self.capture_stop_command);
+
+        let output = &mut settings.output;
+        set(&mut output.separate_hltas, &self.separate_hltas);
+        if self.output_dir.is_some() {
+            output.dir = self.output_dir.clone();
+        }
+        set(&mut output.prefix, &self.prefix);
+        set(&mut output.padding, &self.padding);
+        set(&mut output.game_dir, &self.game_dir);
+        set(&mut output.chunk_frames, &self.chunk_frames);
+        if self.max_lines.is_some() {
+            output.max_lines = self.max_lines;
+        }
+        if self.max_bytes.is_some() {
+            output.max_bytes = self.max_bytes;
+        }
+        set(&mut output.compress, &self.compress);
+    }
+}
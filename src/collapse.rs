@@ -0,0 +1,42 @@
+//! Keeps a frame on screen instead of redrawing it when the next frames look the same.
+//!
+//! Frames are compared with the last one drawn rather than the one just before, so a slow
+//! change still gets drawn once it adds up.
+
+use crate::{render::Dots, screen::Screen, settings::CollapseSettings};
+
+/// Last drawn frame, following frames in order.
+pub struct Collapse {
+    settings: CollapseSettings,
+    screen: Screen,
+}
+
+impl Collapse {
+    pub fn new(settings: &CollapseSettings) -> Self {
+        Self {
+            settings: settings.clone(),
+            screen: Screen::default(),
+        }
+    }
+
+    /// Whether `dots` repeat the frame on screen, which becomes `dots` when they do not.
+    pub fn is_repeat(&mut self, dots: &Dots) -> bool {
+        if !self.settings.enabled {
+            return false;
+        }
+
+        let current = Screen::points_of(dots);
+
+        let changed = self.screen.points().symmetric_difference(&current).count();
+        let size = self.screen.points().len().max(current.len());
+
+        if self.screen.lines_up(dots)
+            && changed as f64 <= self.settings.max_difference * size as f64
+        {
+            return true;
+        }
+
+        self.screen.replace(dots.dimensions, current);
+        false
+    }
+}
@@ -0,0 +1,173 @@
+//! Shortens scripts by writing runs of the same frame as one line.
+//!
+//! A frame bulk turning the view to where it already looks plays the same frame as one keeping
+//! the view, so those turns are dropped first, which is what lets the wait after every dot join
+//! the dot before it.
+
+use crate::hltas::{FrameBulk, Line};
+
+/// `lines` with every frame bulk playing the frame before it again merged into that one through
+/// its repeat count, and frame bulks playing no frame dropped.
+///
+/// Console commands run once when their frame bulk starts, so a frame bulk with a command is
+/// never merged into the one before it.
+pub fn compress(lines: Vec<Line>) -> Vec<Line> {
+    let mut res: Vec<Line> = Vec::with_capacity(lines.len());
+    // `[yaw, pitch]` after the last line, `None` when these lines do not tell
+    let mut view: [Option<f32>; 2] = [None, None];
+
+    for line in lines {
+        let Line::FrameBulk(mut frame_bulk) = line else {
+            // other lines may change how the view behaves
+            view = [None, None];
+            res.push(line);
+            continue;
+        };
+
+        if frame_bulk.repeats == 0 {
+            continue;
+        }
+
+        for (angle, current) in [&mut frame_bulk.yaw, &mut frame_bulk.pitch]
+            .into_iter()
+            .zip(&mut view)
+        {
+            if angle.is_some() && *angle == *current {
+                *angle = None;
+            } else if angle.is_some() {
+                *current = *angle;
+            }
+        }
+
+        if let Some(Line::FrameBulk(previous)) = res.last_mut() {
+            if plays_again(previous, &frame_bulk) {
+                if let Some(repeats) = previous.repeats.checked_add(frame_bulk.repeats) {
+                    previous.repeats = repeats;
+                    continue;
+                }
+            }
+        }
+
+        res.push(frame_bulk.into());
+    }
+
+    res
+}
+
+/// Whether `next` plays the last frame of `previous` again.
+fn plays_again(previous: &FrameBulk, next: &FrameBulk) -> bool {
+    next.console_command.is_none()
+        && next.yaw.is_none()
+        && next.pitch.is_none()
+        && next.frametime == previous.frametime
+        && next.auto_actions == previous.auto_actions
+        && next.movement_keys == previous.movement_keys
+        && next.action_keys == previous.action_keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timing::Quantization;
+
+    const ZERO_MS: f64 = 0.0000000001;
+
+    /// `[yaw, pitch]` the view ends up at after `lines`.
+    fn final_view(lines: &[Line]) -> [Option<f32>; 2] {
+        let mut res = [None, None];
+
+        for line in lines {
+            if let Line::FrameBulk(frame_bulk) = line {
+                res[0] = frame_bulk.yaw.or(res[0]);
+                res[1] = frame_bulk.pitch.or(res[1]);
+            }
+        }
+
+        res
+    }
+
+    /// Checks `compress` keeps how long `lines` play and where they leave the view.
+    fn compressed(lines: Vec<Line>) -> Vec<Line> {
+        let res = compress(lines.clone());
+
+        for quantization in [Quantization::Exact, Quantization::Milliseconds] {
+            assert_eq!(quantization.duration(&res), quantization.duration(&lines));
+        }
+        assert_eq!(final_view(&res), final_view(&lines));
+
+        res
+    }
+
+    #[test]
+    fn merges_waits_into_the_dot_before() {
+        let lines = vec![
+            FrameBulk::look(ZERO_MS, Some(91.), Some(2.))
+                .with_console_command("bxt_force_clear 0; gl_clear 0; sv_zmax 8192")
+                .into(),
+            FrameBulk::wait(ZERO_MS).into(),
+            FrameBulk::look(ZERO_MS, Some(91.), Some(2.)).into(),
+            FrameBulk::wait(ZERO_MS).into(),
+            FrameBulk::look(ZERO_MS, Some(92.), Some(2.)).into(),
+            FrameBulk::wait(ZERO_MS).into(),
+            FrameBulk::look(0.042, Some(90.197754), Some(-0.022)).into(),
+        ];
+
+        let res = compressed(lines);
+
+        assert_eq!(res.len(), 3);
+        let Line::FrameBulk(first) = &res[0] else {
+            panic!("expected a frame bulk, found {:?}", res[0]);
+        };
+        assert_eq!(first.repeats, 4);
+        assert!(first.console_command.is_some());
+    }
+
+    #[test]
+    fn merges_into_a_command_line_with_repeats() {
+        let mut command = FrameBulk::wait(0.001).with_console_command("echo hi");
+        command.repeats = 3;
+        let mut wait = FrameBulk::wait(0.001);
+        wait.repeats = 2;
+
+        let res = compressed(vec![command.into(), wait.into()]);
+
+        let [Line::FrameBulk(merged)] = res.as_slice() else {
+            panic!("expected one frame bulk, found {res:?}");
+        };
+        assert_eq!(merged.repeats, 5);
+        assert_eq!(merged.console_command.as_deref(), Some("echo hi"));
+    }
+
+    #[test]
+    fn keeps_frames_that_play_differently() {
+        let lines = vec![
+            FrameBulk::wait(0.001).into(),
+            // commands run when their frame bulk starts
+            FrameBulk::wait(0.001)
+                .with_console_command("echo hi")
+                .into(),
+            // another frametime
+            FrameBulk::wait(0.002).into(),
+            // another view
+            FrameBulk::look(0.002, Some(10.), None).into(),
+        ];
+
+        assert_eq!(compressed(lines.clone()), lines);
+    }
+
+    #[test]
+    fn drops_turns_to_where_the_view_already_is() {
+        let lines = vec![
+            FrameBulk::look(ZERO_MS, Some(10.), Some(5.)).into(),
+            FrameBulk::look(ZERO_MS, Some(10.), Some(5.)).into(),
+            // other lines may change the view, so it is set again after them
+            Line::Strafing("vectorial".to_string()),
+            FrameBulk::look(ZERO_MS, Some(10.), Some(5.)).into(),
+        ];
+
+        let res = compressed(lines);
+
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[2], FrameBulk::look(ZERO_MS, Some(10.), Some(5.)).into());
+    }
+}
@@ -0,0 +1,63 @@
+//! Draws only what a frame adds to the screen.
+//!
+//! Dots stay on screen until the next clear, so a frame only needs the dots the screen is
+//! missing. Dots the frame no longer has stay behind as stale dots, and once there are too many
+//! of them, or every `keyframe_interval` frames, the screen is cleared and the frame redrawn
+//! whole.
+
+use crate::{render::Dots, screen::Screen, settings::DeltaSettings};
+
+/// What is on screen, following frames in order.
+pub struct Delta {
+    settings: DeltaSettings,
+    screen: Screen,
+    since_keyframe: usize,
+}
+
+impl Delta {
+    pub fn new(settings: &DeltaSettings) -> Self {
+        Self {
+            settings: settings.clone(),
+            screen: Screen::default(),
+            since_keyframe: 0,
+        }
+    }
+
+    /// Dots to draw for the next frame, and whether the screen is cleared before them.
+    pub fn next(&mut self, dots: Dots) -> (Dots, bool) {
+        // strokes are drawn whole
+        if !self.settings.enabled || !dots.strokes.is_empty() {
+            return (dots, true);
+        }
+
+        self.since_keyframe += 1;
+
+        let current = Screen::points_of(&dots);
+        let stale = self.screen.points().difference(&current).count();
+
+        let keyframe = !self.screen.lines_up(&dots)
+            || self.since_keyframe >= self.settings.keyframe_interval
+            || stale as f64 > self.settings.max_stale * current.len() as f64;
+
+        if keyframe {
+            self.screen.replace(dots.dimensions, current);
+            self.since_keyframe = 0;
+            return (dots, true);
+        }
+
+        let points = dots
+            .points
+            .into_iter()
+            .filter(|&point| self.screen.add(point))
+            .collect();
+
+        (
+            Dots {
+                dimensions: dots.dimensions,
+                points,
+                strokes: vec![],
+            },
+            false,
+        )
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{
+    hltas::{FrameBulk, Hltas, Line, Property},
+    order::angle_between,
+    settings::Settings,
+    timing::Quantization,
+    Views,
+};
+
+/// Script loaded once the current one is done.
+#[derive(Debug, Clone)]
+pub struct Chain {
+    /// First frame of the next script, echoed to the console
+    pub frame: usize,
+    /// Path given to `bxt_tas_loadscript`, relative to the mod folder
+    pub path: String,
+}
+
+/// Writes view angles as script lines.
+pub trait Emitter: Send + Sync {
+    /// Lines drawing one frame, only clearing the screen when there is nothing to draw.
+    fn frame(&self, views: &Views) -> Vec<Line>;
+
+    /// Lines drawing `views` over what is already on screen, empty when there is nothing to draw.
+    fn frame_over(&self, views: &Views) -> Vec<Line>;
+
+    /// Lines keeping the drawn frame on screen for `duration` seconds.
+    fn delay(&self, duration: f64) -> Vec<Line>;
+
+    /// Lines running a console command, taking as little time as possible.
+    fn command(&self, command: &str) -> Vec<Line>;
+
+    /// Complete script around the frames in `body`.
+    ///
+    /// `next` is loaded once this one is done. Without `next` the body must come last, so a
+    /// single script can be streamed out frame by frame.
+    fn script(&self, body: Vec<Line>, next: Option<Chain>) -> Hltas;
+}
+
+enum Clear {
+    None,
+    Yes,
+    No,
+}
+
+/// Emits HLTAS for bxt, one 0ms frame per dot.
+pub struct HltasEmitter {
+    zero_ms_frametime: f64,
+    slow_wait: Option<f64>,
+    starting_yaw: f64,
+    starting_pitch: f64,
+    /// Most degrees the view turns in one frame
+    max_angle_step: Option<f64>,
+    /// Frames draw over each other, so loading a script must not clear the screen
+    keep_screen: bool,
+}
+
+impl HltasEmitter {
+    pub fn new(settings: &Settings) -> Self {
+        let timing = &settings.timing;
+
+        Self {
+            zero_ms_frametime: timing.zero_ms_frametime,
+            slow_wait: timing.slow_draw.then(|| {
+                Quantization::new(timing).representable(timing.slow_wait, timing.zero_ms_frametime)
+            }),
+            starting_yaw: settings.projection.starting_yaw,
+            starting_pitch: settings.projection.starting_pitch,
+            max_angle_step: settings.max_angle_step,
+            keep_screen: settings.delta.enabled,
+        }
+    }
+
+    /// `shown` shows or hides the brush drawing the picture from this frame on, `sv_zmax 1`
+    /// clips it away.
+    fn change_view_frame(
+        &self,
+        pitch: f32,
+        yaw: f32,
+        should_clear: Clear,
+        shown: Option<bool>,
+    ) -> FrameBulk {
+        let frame_bulk = FrameBulk::look(self.zero_ms_frametime, Some(yaw), Some(pitch));
+
+        let clear = match should_clear {
+            Clear::None => None,
+            Clear::Yes => Some("bxt_force_clear 1; gl_clear 1"),
+            Clear::No => Some("bxt_force_clear 0; gl_clear 0"),
+        };
+        let zmax = shown.map(|on| if on { "sv_zmax 8192" } else { "sv_zmax 1" });
+
+        match (clear, zmax) {
+            (None, None) => frame_bulk,
+            (Some(command), None) | (None, Some(command)) => {
+                frame_bulk.with_console_command(command)
+            }
+            (Some(clear), Some(zmax)) => {
+                frame_bulk.with_console_command(format!("{clear}; {zmax}"))
+            }
+        }
+    }
+
+    /// Views between `from` and `to` keeping every turn under the max angle step, ends excluded.
+    fn steps(&self, from: [f32; 2], to: [f32; 2]) -> Vec<[f32; 2]> {
+        let Some(max_angle_step) = self.max_angle_step else {
+            return vec![];
+        };
+
+        let count = (angle_between(from, to) / max_angle_step).ceil() as usize;
+        let pitch = to[0] as f64 - from[0] as f64;
+        let yaw = (to[1] as f64 - from[1] as f64 + 180.).rem_euclid(360.) - 180.;
+
+        (1..count)
+            .map(|step| {
+                let t = step as f64 / count as f64;
+                [
+                    (from[0] as f64 + pitch * t) as f32,
+                    (from[1] as f64 + yaw * t) as f32,
+                ]
+            })
+            .collect()
+    }
+
+    /// Lines drawing `views`, clearing the screen first when `clear` is set.
+    fn draw(&self, views: &Views, clear: bool) -> Vec<Line> {
+        if views.is_empty() && clear {
+            // the frame before would stay on screen otherwise
+            return vec![
+                FrameBulk::wait(self.zero_ms_frametime)
+                    .with_console_command("bxt_force_clear 1; gl_clear 1; sv_zmax 1")
+                    .into(),
+                FrameBulk::wait(self.zero_ms_frametime)
+                    .with_console_command("bxt_force_clear 0; gl_clear 0")
+                    .into(),
+            ];
+        }
+
+        if views.is_empty() {
+            return vec![];
+        }
+
+        // turns bigger than the max angle step go through hidden views, the view comes back to
+        // the start where the delay looks
+        let start = [self.starting_pitch as f32, self.starting_yaw as f32];
+        let mut path = vec![];
+        let mut previous = start;
+
+        for &view in views {
+            path.extend(
+                self.steps(previous, view)
+                    .into_iter()
+                    .map(|step| (step, false)),
+            );
+            path.push((view, true));
+            previous = view;
+        }
+
+        path.extend(
+            self.steps(previous, start)
+                .into_iter()
+                .map(|step| (step, false)),
+        );
+
+        let mut res = vec![];
+        // drawing over the screen never clears it
+        let mut started = !clear;
+        let mut clearing = false;
+        // the last frame may have ended with the brush hidden
+        let mut shown = None;
+
+        for ([pitch, yaw], visible) in path {
+            // clearing stops at the next dot, hidden views in between are not drawn anyway
+            let should_clear = if visible && clearing {
+                clearing = false;
+                Clear::No
+            } else if visible && !started {
+                started = true;
+                clearing = true;
+                Clear::Yes
+            } else {
+                Clear::None
+            };
+
+            // the first dot is wiped by clearing, so the brush only shows from the next one on
+            let show = visible && !clearing;
+            let toggle_shown = (shown != Some(show)).then(|| {
+                shown = Some(show);
+                show
+            });
+
+            res.push(
+                self.change_view_frame(pitch, yaw, should_clear, toggle_shown)
+                    .into(),
+            );
+
+            if let Some(slow_wait) = self.slow_wait.filter(|_| visible) {
+                res.push(FrameBulk::wait(slow_wait).into());
+            }
+        }
+
+        res
+    }
+}
+
+impl Emitter for HltasEmitter {
+    fn frame(&self, views: &Views) -> Vec<Line> {
+        self.draw(views, true)
+    }
+
+    fn frame_over(&self, views: &Views) -> Vec<Line> {
+        self.draw(views, false)
+    }
+
+    fn delay(&self, duration: f64) -> Vec<Line> {
+        vec![FrameBulk::look(
+            duration,
+            Some(self.starting_yaw as f32),
+            Some(self.starting_pitch as f32),
+        )
+        .into()]
+    }
+
+    fn command(&self, command: &str) -> Vec<Line> {
+        vec![FrameBulk::wait(self.zero_ms_frametime)
+            .with_console_command(command)
+            .into()]
+    }
+
+    fn script(&self, body: Vec<Line>, next: Option<Chain>) -> Hltas {
+        let mut lines = vec![
+            Line::Strafing("vectorial".to_string()),
+            Line::TargetYaw("velocity_lock".to_string()),
+            // need to have at least 2 frames in a hltas
+            FrameBulk::look(self.zero_ms_frametime, Some(0.), None).into(),
+        ];
+
+        lines.extend(body);
+
+        if let Some(next) = next {
+            lines.push(
+                FrameBulk::look(self.zero_ms_frametime, Some(0.), None)
+                    .with_console_command(format!(
+                        "echo \"frame {}\"; bxt_tas_loadscript {}",
+                        next.frame, next.path
+                    ))
+                    .into(),
+            );
+        }
+
+        Hltas {
+            properties: vec![
+                Property::HlstrafeVersion(5),
+                Property::LoadCommand(
+                    if self.keep_screen {
+                        "bxt_anglespeed_cap 0;"
+                    } else {
+                        "bxt_anglespeed_cap 0; gl_clear 1; bxt_force_clear 1; sv_zmax 1;"
+                    }
+                    .to_string(),
+                ),
+                Property::FrameTime0ms(self.zero_ms_frametime),
+            ],
+            lines,
+            ..Hltas::default()
+        }
+    }
+}
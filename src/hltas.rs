@@ -0,0 +1,304 @@
+//! Typed HLTAS v1 scripts.
+//!
+//! Everything that ends up in a script is built from these types and written with their
+//! [`Display`](fmt::Display) impls, so there is exactly one place that knows the format.
+
+use std::fmt;
+
+mod parse;
+
+pub use parse::ParseError;
+
+/// A whole script, `version 1` header included.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hltas {
+    pub properties: Vec<Property>,
+    pub lines: Vec<Line>,
+    pub line_ending: LineEnding,
+    /// The last line has no line ending, like the chained scripts of the original template
+    pub unterminated: bool,
+}
+
+/// What every line of a script ends with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Header line between `version 1` and `frames`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Property {
+    /// `hlstrafe_version 5`
+    HlstrafeVersion(u32),
+    /// `load_command ...`, console command ran when the script loads
+    LoadCommand(String),
+    /// `frametime0ms ...`, what bxt uses for 0ms frames
+    FrameTime0ms(f64),
+    /// `demo ...`
+    Demo(String),
+    /// `save ...`
+    Save(String),
+    /// `seed ...`
+    Seed(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    FrameBulk(FrameBulk),
+    /// `strafing vectorial`
+    Strafing(String),
+    /// `target_yaw velocity_lock`
+    TargetYaw(String),
+    /// `// comment`
+    Comment(String),
+    /// Any other directive (`save`, `reset`, `buttons`, ...), kept verbatim.
+    Directive {
+        name: String,
+        args: String,
+    },
+    /// Empty line, kept so scripts are written back as they were
+    Blank,
+}
+
+/// `----------|------|------|0.001|90|0|1|echo hi`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameBulk {
+    pub auto_actions: AutoActions,
+    pub movement_keys: MovementKeys,
+    pub action_keys: ActionKeys,
+    pub frametime: f64,
+    /// `None` keeps the current yaw
+    pub yaw: Option<f32>,
+    /// `None` keeps the current pitch
+    pub pitch: Option<f32>,
+    pub repeats: u32,
+    pub console_command: Option<String>,
+}
+
+/// First field, `s03lj-bcgw` style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoActions {
+    /// `s<type><dir>`
+    pub strafe: Option<Strafe>,
+    /// lgagst, autojump or ducktap, jumpbug, dbc, dbg and dwj, `-` when off
+    pub toggles: [char; 7],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Strafe {
+    pub kind: u8,
+    pub dir: u8,
+}
+
+/// Second field, `flrbud`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MovementKeys {
+    pub forward: bool,
+    pub left: bool,
+    pub right: bool,
+    pub back: bool,
+    pub up: bool,
+    pub down: bool,
+}
+
+/// Third field, `jdu12r`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ActionKeys {
+    pub jump: bool,
+    pub duck: bool,
+    pub use_: bool,
+    pub attack_1: bool,
+    pub attack_2: bool,
+    pub reload: bool,
+}
+
+impl Default for AutoActions {
+    fn default() -> Self {
+        Self {
+            strafe: None,
+            toggles: ['-'; 7],
+        }
+    }
+}
+
+impl FrameBulk {
+    /// No keys held, one frame of `frametime`.
+    pub fn wait(frametime: f64) -> Self {
+        Self {
+            auto_actions: AutoActions::default(),
+            movement_keys: MovementKeys::default(),
+            action_keys: ActionKeys::default(),
+            frametime,
+            yaw: None,
+            pitch: None,
+            repeats: 1,
+            console_command: None,
+        }
+    }
+
+    /// One frame of `frametime` looking at `yaw` and `pitch`.
+    pub fn look(frametime: f64, yaw: Option<f32>, pitch: Option<f32>) -> Self {
+        Self {
+            yaw,
+            pitch,
+            ..Self::wait(frametime)
+        }
+    }
+
+    pub fn with_console_command(mut self, command: impl Into<String>) -> Self {
+        self.console_command = Some(command.into());
+        self
+    }
+}
+
+impl From<FrameBulk> for Line {
+    fn from(frame_bulk: FrameBulk) -> Self {
+        Self::FrameBulk(frame_bulk)
+    }
+}
+
+impl fmt::Display for Hltas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ending = self.line_ending.as_str();
+
+        // every line but the first starts by ending the one before
+        write!(f, "version 1")?;
+
+        for property in &self.properties {
+            write!(f, "{ending}{property}")?;
+        }
+
+        write!(f, "{ending}frames")?;
+
+        for line in &self.lines {
+            write!(f, "{ending}{line}")?;
+        }
+
+        if !self.unterminated {
+            write!(f, "{ending}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Property {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Property::HlstrafeVersion(version) => write!(f, "hlstrafe_version {version}"),
+            Property::LoadCommand(command) => write!(f, "load_command {command}"),
+            Property::FrameTime0ms(frametime) => write!(f, "frametime0ms {frametime}"),
+            Property::Demo(demo) => write!(f, "demo {demo}"),
+            Property::Save(save) => write!(f, "save {save}"),
+            Property::Seed(seed) => write!(f, "seed {seed}"),
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Line::FrameBulk(frame_bulk) => frame_bulk.fmt(f),
+            Line::Strafing(kind) => write!(f, "strafing {kind}"),
+            Line::TargetYaw(target) => write!(f, "target_yaw {target}"),
+            Line::Comment(comment) => write!(f, "//{comment}"),
+            Line::Directive { name, args } if args.is_empty() => write!(f, "{name}"),
+            Line::Directive { name, args } => write!(f, "{name} {args}"),
+            Line::Blank => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for FrameBulk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}|{}|",
+            self.auto_actions, self.movement_keys, self.action_keys, self.frametime
+        )?;
+
+        match self.yaw {
+            Some(yaw) => write!(f, "{yaw}|")?,
+            None => write!(f, "-|")?,
+        }
+
+        match self.pitch {
+            Some(pitch) => write!(f, "{pitch}|")?,
+            None => write!(f, "-|")?,
+        }
+
+        write!(f, "{}", self.repeats)?;
+
+        if let Some(command) = &self.console_command {
+            write!(f, "|{command}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for AutoActions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.strafe {
+            Some(Strafe { kind, dir }) => write!(f, "s{kind}{dir}")?,
+            None => write!(f, "---")?,
+        }
+
+        for toggle in self.toggles {
+            write!(f, "{toggle}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_keys(f: &mut fmt::Formatter<'_>, keys: [(bool, char); 6]) -> fmt::Result {
+    for (held, key) in keys {
+        write!(f, "{}", if held { key } else { '-' })?;
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for MovementKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_keys(
+            f,
+            [
+                (self.forward, 'f'),
+                (self.left, 'l'),
+                (self.right, 'r'),
+                (self.back, 'b'),
+                (self.up, 'u'),
+                (self.down, 'd'),
+            ],
+        )
+    }
+}
+
+impl fmt::Display for ActionKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_keys(
+            f,
+            [
+                (self.jump, 'j'),
+                (self.duck, 'd'),
+                (self.use_, 'u'),
+                (self.attack_1, '1'),
+                (self.attack_2, '2'),
+                (self.reload, 'r'),
+            ],
+        )
+    }
+}
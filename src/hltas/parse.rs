@@ -0,0 +1,455 @@
+use std::fmt;
+
+use super::{
+    ActionKeys, AutoActions, FrameBulk, Hltas, Line, LineEnding, MovementKeys, Property, Strafe,
+};
+
+/// Directives kept as [`Line::Directive`].
+const DIRECTIVES: &[&str] = &[
+    "save",
+    "seed",
+    "buttons",
+    "lgagstminspeed",
+    "reset",
+    "change",
+    "target_yaw_override",
+    "render_yaw_override",
+];
+
+/// What is wrong with a line, numbered from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Hltas {
+    /// Reads a script back, every malformed line is reported instead of stopping at the first.
+    pub fn parse(text: &str) -> Result<Self, Vec<ParseError>> {
+        let mut res = Hltas {
+            // the first line decides, mixed endings are not written back
+            line_ending: match text.split_inclusive('\n').next() {
+                Some(first) if first.ends_with("\r\n") => LineEnding::CrLf,
+                _ => LineEnding::Lf,
+            },
+            unterminated: !text.is_empty() && !text.ends_with('\n'),
+            ..Hltas::default()
+        };
+        let mut errors = vec![];
+
+        let mut lines = text.lines().enumerate().map(|(idx, line)| (idx + 1, line));
+
+        match lines.next() {
+            Some((_, "version 1")) => (),
+            Some((line, other)) => errors.push(ParseError {
+                line,
+                message: format!("expected `version 1`, found `{other}`"),
+            }),
+            None => {
+                return Err(vec![ParseError {
+                    line: 1,
+                    message: "empty script".to_string(),
+                }])
+            }
+        }
+
+        let mut found_frames = false;
+
+        for (line, text) in lines.by_ref() {
+            if text == "frames" {
+                found_frames = true;
+                break;
+            }
+
+            match parse_property(text) {
+                Ok(property) => res.properties.push(property),
+                Err(message) => errors.push(ParseError { line, message }),
+            }
+        }
+
+        if !found_frames {
+            errors.push(ParseError {
+                line: text.lines().count(),
+                message: "missing `frames`".to_string(),
+            });
+        }
+
+        for (line, text) in lines {
+            if text.trim().is_empty() {
+                res.lines.push(Line::Blank);
+                continue;
+            }
+
+            match parse_line(text) {
+                Ok(parsed) => res.lines.push(parsed),
+                Err(message) => errors.push(ParseError { line, message }),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(res)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parses `text` and checks that writing it back gives the exact same bytes.
+    pub fn parse_round_trip(text: &str) -> Result<Self, Vec<ParseError>> {
+        let res = Self::parse(text)?;
+        let written = res.to_string();
+
+        if written == text {
+            return Ok(res);
+        }
+
+        let mut original_lines = text.split_inclusive('\n');
+        let mut written_lines = written.split_inclusive('\n');
+        let mut line = 1;
+
+        let message = loop {
+            let (original, written) = match (original_lines.next(), written_lines.next()) {
+                (Some(original), Some(written)) if original == written => {
+                    line += 1;
+                    continue;
+                }
+                (Some(original), Some(written)) => (original, written),
+                (Some(original), None) => break format!("{original:?} is not written back"),
+                (None, Some(written)) => break format!("{written:?} is written back as well"),
+                (None, None) => unreachable!("the texts differ"),
+            };
+
+            let (original, original_ending) = split_line_ending(original);
+            let (written, written_ending) = split_line_ending(written);
+
+            break if original != written {
+                format!("written back as {written:?} instead of {original:?}")
+            } else if original_ending.is_empty() {
+                "trailing newline added".to_string()
+            } else if written_ending.is_empty() {
+                "missing trailing newline".to_string()
+            } else {
+                format!(
+                    "line ending written back as {written_ending:?} instead of {original_ending:?}"
+                )
+            };
+        };
+
+        Err(vec![ParseError { line, message }])
+    }
+}
+
+/// `line` without its line ending, and the line ending.
+fn split_line_ending(line: &str) -> (&str, &str) {
+    let len = line.trim_end_matches(['\r', '\n']).len();
+    line.split_at(len)
+}
+
+fn parse_property(text: &str) -> Result<Property, String> {
+    let (name, value) = text.split_once(' ').unwrap_or((text, ""));
+
+    let res = match name {
+        "hlstrafe_version" => Property::HlstrafeVersion(
+            value
+                .parse()
+                .map_err(|_| format!("invalid hlstrafe_version `{value}`"))?,
+        ),
+        "load_command" => Property::LoadCommand(value.to_string()),
+        "frametime0ms" => Property::FrameTime0ms(parse_frametime(value)?),
+        "demo" => Property::Demo(value.to_string()),
+        "save" => Property::Save(value.to_string()),
+        "seed" => Property::Seed(value.to_string()),
+        _ => return Err(format!("unknown property `{name}`")),
+    };
+
+    Ok(res)
+}
+
+fn parse_line(text: &str) -> Result<Line, String> {
+    if let Some(comment) = text.strip_prefix("//") {
+        return Ok(Line::Comment(comment.to_string()));
+    }
+
+    if text.contains('|') {
+        return parse_frame_bulk(text).map(Line::FrameBulk);
+    }
+
+    let (name, args) = text.split_once(' ').unwrap_or((text, ""));
+
+    match name {
+        "strafing" => Ok(Line::Strafing(args.to_string())),
+        "target_yaw" => Ok(Line::TargetYaw(args.to_string())),
+        _ if DIRECTIVES.contains(&name) => Ok(Line::Directive {
+            name: name.to_string(),
+            args: args.to_string(),
+        }),
+        _ => Err(format!("unknown line `{text}`")),
+    }
+}
+
+fn parse_frame_bulk(text: &str) -> Result<FrameBulk, String> {
+    let fields: Vec<&str> = text.splitn(8, '|').collect();
+
+    if fields.len() < 7 {
+        return Err(format!(
+            "frame line needs at least 7 fields, found {}",
+            fields.len()
+        ));
+    }
+
+    Ok(FrameBulk {
+        auto_actions: parse_auto_actions(fields[0])?,
+        movement_keys: parse_movement_keys(fields[1])?,
+        action_keys: parse_action_keys(fields[2])?,
+        frametime: parse_frametime(fields[3])?,
+        yaw: parse_angle(fields[4], "yaw")?,
+        pitch: parse_angle(fields[5], "pitch")?,
+        repeats: fields[6]
+            .parse()
+            .ok()
+            .filter(|&repeats| repeats > 0)
+            .ok_or_else(|| format!("invalid repeat count `{}`", fields[6]))?,
+        console_command: fields.get(7).map(|command| command.to_string()),
+    })
+}
+
+fn parse_auto_actions(field: &str) -> Result<AutoActions, String> {
+    let chars: Vec<char> = field.chars().collect();
+    let error = || format!("invalid auto actions `{field}`");
+
+    if chars.len() != 10 {
+        return Err(error());
+    }
+
+    let strafe = match chars[..3] {
+        ['-', '-', '-'] => None,
+        ['s', kind, dir] => Some(Strafe {
+            kind: kind.to_digit(10).ok_or_else(error)? as u8,
+            dir: dir.to_digit(10).ok_or_else(error)? as u8,
+        }),
+        _ => return Err(error()),
+    };
+
+    let mut toggles = ['-'; 7];
+    for (toggle, &c) in toggles.iter_mut().zip(&chars[3..]) {
+        if c != '-' && !c.is_ascii_alphanumeric() {
+            return Err(error());
+        }
+
+        *toggle = c;
+    }
+
+    Ok(AutoActions { strafe, toggles })
+}
+
+/// Every position is either `-` or its own key letter.
+fn parse_keys(field: &str, letters: &str, what: &str) -> Result<[bool; 6], String> {
+    let mut res = [false; 6];
+
+    if field.chars().count() != letters.len() {
+        return Err(format!("invalid {what} `{field}`"));
+    }
+
+    for ((held, c), letter) in res.iter_mut().zip(field.chars()).zip(letters.chars()) {
+        *held = if c == letter {
+            true
+        } else if c == '-' {
+            false
+        } else {
+            return Err(format!("invalid {what} `{field}`"));
+        };
+    }
+
+    Ok(res)
+}
+
+fn parse_movement_keys(field: &str) -> Result<MovementKeys, String> {
+    let [forward, left, right, back, up, down] = parse_keys(field, "flrbud", "movement keys")?;
+
+    Ok(MovementKeys {
+        forward,
+        left,
+        right,
+        back,
+        up,
+        down,
+    })
+}
+
+fn parse_action_keys(field: &str) -> Result<ActionKeys, String> {
+    let [jump, duck, use_, attack_1, attack_2, reload] =
+        parse_keys(field, "jdu12r", "action keys")?;
+
+    Ok(ActionKeys {
+        jump,
+        duck,
+        use_,
+        attack_1,
+        attack_2,
+        reload,
+    })
+}
+
+fn parse_frametime(field: &str) -> Result<f64, String> {
+    field
+        .parse::<f64>()
+        .ok()
+        .filter(|frametime| frametime.is_finite() && *frametime >= 0.)
+        .ok_or_else(|| format!("invalid frametime `{field}`"))
+}
+
+fn parse_angle(field: &str, what: &str) -> Result<Option<f32>, String> {
+    if field == "-" {
+        return Ok(None);
+    }
+
+    field
+        .parse::<f32>()
+        .ok()
+        .filter(|angle| angle.is_finite())
+        .map(Some)
+        .ok_or_else(|| format!("invalid {what} `{field}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        emit::{Chain, Emitter, HltasEmitter},
+        settings::Settings,
+    };
+
+    /// What the original `hltas_template` wrote for the last frame of a video.
+    const TEMPLATE: &str = "\
+version 1
+hlstrafe_version 5
+load_command bxt_anglespeed_cap 0; gl_clear 1; bxt_force_clear 1; sv_zmax 1;
+frametime0ms 0.0000000001
+frames
+strafing vectorial
+target_yaw velocity_lock
+
+----------|------|------|0.0000000001|0|-|1
+----------|------|------|0.0000000001|90.197754|-0.022|1|bxt_force_clear 1; gl_clear 1; sv_zmax 1
+----------|------|------|0.000001|-|-|1|
+----------|------|------|0.0000000001|91.197754|-0.022|1|bxt_force_clear 0; gl_clear 0; sv_zmax 8192
+----------|------|------|0.000001|-|-|1|
+----------|------|------|0.04171|90.197754|-0.022|1
+
+";
+
+    #[test]
+    fn round_trips_template() {
+        let hltas = Hltas::parse_round_trip(TEMPLATE).unwrap();
+
+        assert_eq!(hltas.lines[2], Line::Blank);
+        assert_eq!(hltas.lines.last(), Some(&Line::Blank));
+    }
+
+    #[test]
+    fn round_trips_emitted_script() {
+        let emitter = HltasEmitter::new(&Settings::default());
+        let mut body = emitter.frame(&vec![[90., 10.], [91.5, -3.25]]);
+        body.extend(emitter.delay(0.042));
+
+        let chain = Chain {
+            frame: 1,
+            path: "out/1.hltas".to_string(),
+        };
+        let hltas = emitter.script(body, Some(chain));
+        let text = hltas.to_string();
+
+        assert_eq!(Hltas::parse_round_trip(&text), Ok(hltas));
+    }
+
+    #[test]
+    fn reports_malformed_lines() {
+        let text = "\
+version 1
+hlstrafe_version five
+frames
+----------|------|------|0.001|-|-|1
+----------|------|------|fast|-|-|1
+----------|------|------|0.001|-|-|0
+jump around
+----------|------
+";
+
+        let lines: Vec<usize> = Hltas::parse(text)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.line)
+            .collect();
+
+        assert_eq!(lines, [2, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn reports_missing_frames() {
+        let errors = Hltas::parse("version 1\nhlstrafe_version 5\n").unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 2);
+        assert_eq!(errors[0].message, "missing `frames`");
+    }
+
+    #[test]
+    fn round_trips_chained_template() {
+        // chained scripts ended with the load command, without a newline
+        let text = TEMPLATE.trim_end().to_string()
+            + "\n----------|------|------|0.0000000001|0|-|1|echo \"frame 1\"; bxt_tas_loadscript out/1.hltas";
+        let hltas = Hltas::parse_round_trip(&text).unwrap();
+
+        assert!(hltas.unterminated);
+        assert_eq!(hltas.to_string(), text);
+    }
+
+    #[test]
+    fn round_trips_crlf() {
+        let text = TEMPLATE.replace('\n', "\r\n");
+        let hltas = Hltas::parse_round_trip(&text).unwrap();
+
+        assert_eq!(hltas.line_ending, LineEnding::CrLf);
+        assert_eq!(hltas.lines[2], Line::Blank);
+    }
+
+    #[test]
+    fn reports_first_difference_when_writing_back() {
+        let text = TEMPLATE.replace("0.04171|90.197754", "0.041710|90.197754");
+        let errors = Hltas::parse_round_trip(&text).unwrap_err();
+
+        assert_eq!(errors[0].line, 14);
+        assert_eq!(
+            errors[0].message,
+            r#"written back as "----------|------|------|0.04171|90.197754|-0.022|1" instead of "----------|------|------|0.041710|90.197754|-0.022|1""#
+        );
+    }
+
+    #[test]
+    fn reports_mixed_line_endings() {
+        let text = TEMPLATE.replacen("frames\n", "frames\r\n", 1);
+        let errors = Hltas::parse_round_trip(&text).unwrap_err();
+
+        assert_eq!(errors[0].line, 5);
+        assert_eq!(
+            errors[0].message,
+            r#"line ending written back as "\n" instead of "\r\n""#
+        );
+    }
+
+    #[test]
+    fn reports_whitespace_differences() {
+        let text = TEMPLATE.replacen("\n\n", "\n  \n", 1);
+        let errors = Hltas::parse_round_trip(&text).unwrap_err();
+
+        assert_eq!(errors[0].line, 8);
+        assert_eq!(errors[0].message, r#"written back as "" instead of "  ""#);
+    }
+}
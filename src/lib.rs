@@ -0,0 +1,37 @@
+//! Turns videos and images into HLTAS scripts that draw them by turning the view over a white
+//! brush while the screen is not cleared.
+//!
+//! A conversion is a [`Pipeline`]: frames come from a [`source::FrameSource`], a
+//! [`render::Renderer`] turns each frame into dots, the dots are projected into view angles and an
+//! [`emit::Emitter`] writes the script. Every stage is a trait so it can be swapped out.
+
+use serde::{Deserialize, Serialize};
+
+pub mod adaptive;
+pub mod calibrate;
+pub mod collapse;
+pub mod compress;
+pub mod delta;
+pub mod emit;
+pub mod hltas;
+pub mod order;
+pub mod pipeline;
+pub mod preview;
+pub mod projection;
+pub mod render;
+pub mod screen;
+pub mod settings;
+pub mod source;
+pub mod timing;
+pub mod vector;
+pub mod writer;
+
+pub use pipeline::Pipeline;
+
+/// `[pitch, yaw]` of every dot in a frame, in drawing order.
+pub type Views = Vec<[f32; 2]>;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Frame {
+    pub viewangles: Views,
+}
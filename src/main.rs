@@ -1,349 +1,406 @@
 use std::{
-    fs::OpenOptions,
-    io::{Cursor, Write},
-    path::Path,
-    thread,
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
 };
 
+use bad_apple_to_hltas::{
+    calibrate::{self, Target},
+    hltas::{Hltas, Line},
+    pipeline::ConvertError,
+    preview::Preview,
+    settings::Settings,
+    source::{AnimationSource, FrameSource, ImageSource, Resampled, SequenceSource, VideoSource},
+    timing::{Quantization, Timeline},
+    writer::{self, AtomicFile},
+    Pipeline,
+};
+use clap::{error::ErrorKind, CommandFactory, Parser};
 use image::{
-    imageops::{self, BiLevel},
-    DynamicImage, GenericImageView, GrayImage,
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, DynamicImage, Frame,
 };
-use vid2img::FileSource;
-
-use serde::{Deserialize, Serialize};
-
-//
-//
-// SETTINGS HERE
-//
-//
-
-// scaling image
-const SCALE_FACTOR: f32 = 0.125;
-
-// cannny parameters
-const SIGMA: f32 = 1.2;
-const STRONG_THRESHOLD: f32 = 0.2;
-const WEAK_THRESHOLD: f32 = 0.01;
 
-// origin
-const STARTING_YAW: f32 = 90.197754;
-const STARTING_PITCH: f32 = -0.022000;
-
-const SCREEN_WIDTH: u32 = 1280;
-const SCREEN_HEIGHT: u32 = 720;
-const ANGLE_PER_PIXEL: f32 = 0.0625 / 2.;
-
-const ZERO_MS_FRAMETIME: &str = "0.0000000001";
-const HLTAS_FRAMETIME: &str = "0.04171"; // video is 23.97602fps
-
-// DRAW with some wait in between
-const SLOW_DRAW: bool = true;
-const SLOW_WAIT: &str = "0.000001";
-
-// Caps dot count on screen
-const COUNT_DOTS: bool = false;
-const MAX_DOTS: usize = 240;
+use cli::{Cli, Command, ConfigFormat};
+
+mod cli;
+
+const MAX_REPORTED_ERRORS: usize = 20;
+
+/// Effective settings for a conversion, exits after printing them with `--dump-config`.
+fn settings(cli: &Cli) -> Settings {
+    let mut settings = match &cli.config {
+        Some(path) => Settings::from_config_file(path, cli.preset.as_deref())
+            .unwrap_or_else(|err| Cli::command().error(ErrorKind::Io, err).exit()),
+        None => cli
+            .preset
+            .as_deref()
+            .map(|name| Settings::preset(name).expect("preset names are checked by clap"))
+            .unwrap_or_default(),
+    };
+
+    match &cli.command {
+        Command::Video {
+            video_width,
+            video_height,
+            output,
+            input_args,
+            settings: args,
+            ..
+        } => {
+            args.apply(&mut settings);
+            input_args.apply(&mut settings);
+
+            if output.is_some() {
+                settings.output.separate_hltas = false;
+            }
 
-// Change mode of image
-const MODE: Mode = Mode::Dithering;
+            if let Some(width) = video_width {
+                settings.video_dimension.0 = *width;
+            }
+            if let Some(height) = video_height {
+                settings.video_dimension.1 = *height;
+            }
+        }
+        Command::Sequence {
+            output,
+            input_args,
+            settings: args,
+            ..
+        }
+        | Command::Animation {
+            output,
+            input_args,
+            settings: args,
+            ..
+        } => {
+            args.apply(&mut settings);
+            input_args.apply(&mut settings);
+
+            if output.is_some() {
+                settings.output.separate_hltas = false;
+            }
+        }
+        Command::Image { settings: args, .. }
+        | Command::Preview { settings: args, .. }
+        | Command::Calibrate { settings: args, .. } => args.apply(&mut settings),
+        Command::Check { .. } => (),
+    }
 
-const VIDEO_PATH: &str = "/home/khang/apple/renai_circulation.webm";
-const VIDEO_DIMENSION: (u32, u32) = (1280, 720);
+    if let Err(err) = settings.validate() {
+        Cli::command().error(ErrorKind::ValueValidation, err).exit();
+    }
 
-// one video per frame
-const SEPARATE_HLTAS: bool = true;
+    if let Some(format) = cli.dump_config {
+        match format {
+            ConfigFormat::Toml => print!("{}", settings.to_toml()),
+            ConfigFormat::Json => println!("{}", settings.to_json()),
+        }
 
-//
-//
-// DONT GO BEYOND HERE
-//
-//
+        std::process::exit(0);
+    }
 
-enum Mode {
-    /// Detects edges from the video
-    CannyEdge,
-    /// Dithers the video
-    Dithering,
-    /// Just black and white aka pre-processed video
-    BiLevel,
+    settings
 }
 
-type Views = Vec<[f32; 2]>;
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Frame {
-    viewangles: Vec<[f32; 2]>,
+/// Tells about timing settings the engine will not play as asked.
+fn warn_timing(settings: &Settings) {
+    for warning in Quantization::new(&settings.timing).warnings(&settings.timing) {
+        eprintln!("warning: {warning}");
+    }
 }
 
-fn resize_image(img: DynamicImage) -> DynamicImage {
-    let dimensions = img.dimensions();
-    img.resize(
-        (dimensions.0 as f32 * SCALE_FACTOR) as u32,
-        (dimensions.1 as f32 * SCALE_FACTOR) as u32,
-        imageops::FilterType::Nearest,
-    )
+/// Streams one script to `output`, stdout when it is `None` or `-`.
+fn convert_to_single(
+    pipeline: &Pipeline,
+    source: &mut dyn FrameSource,
+    output: Option<&Path>,
+) -> Result<Timeline, String> {
+    match output.filter(|path| *path != Path::new("-")) {
+        Some(path) => AtomicFile::create(path)
+            .map_err(ConvertError::Write)
+            .and_then(|mut file| {
+                let timeline = pipeline.convert_to_writer(source, &mut file)?;
+                file.commit()?;
+                Ok(timeline)
+            })
+            .map_err(|err| err.message(path.display())),
+        None => pipeline
+            .convert_to_writer(source, &mut BufWriter::new(io::stdout().lock()))
+            .map_err(|err| err.message("to stdout")),
+    }
 }
 
-fn process_frame(img: DynamicImage) -> Views {
-    let mut res: Views = vec![];
+/// Writes chained scripts into the output folder, `out` next to `input` by default, or one
+/// script to `output`.
+///
+/// When the frame rate is known the delay frametime follows it.
+fn convert(mut settings: Settings, source: impl FrameSource, input: &Path, output: Option<&Path>) {
+    let mut source = Resampled::new(source, &settings.input, settings.timing.frametime)
+        .unwrap_or_else(|err| Cli::command().error(ErrorKind::ValueValidation, err).exit());
 
-    match MODE {
-        Mode::CannyEdge => edge_detection(img, &mut res),
-        Mode::Dithering => dithering(img, &mut res),
-        Mode::BiLevel => bilevel(img, &mut res),
+    if let Some(fps) = source.fps() {
+        settings.timing.frametime = 1. / fps;
     }
 
-    res
-}
-
-fn edge_detection(img: impl Into<GrayImage>, res: &mut Views) {
-    let detection = edge_detection::canny(
-        img,
-        SIGMA,            // sigma
-        STRONG_THRESHOLD, // strong threshold
-        WEAK_THRESHOLD,   // weak threshold
+    warn_timing(&settings);
+
+    let output_dir = settings
+        .output
+        .dir
+        .clone()
+        .unwrap_or_else(|| input.with_file_name("out"));
+    let pipeline = Pipeline::new(settings);
+
+    let res = if pipeline.settings().output.separate_hltas {
+        pipeline.convert_to_folder(&mut source, &output_dir)
+    } else {
+        convert_to_single(&pipeline, &mut source, output)
+    };
+
+    let timeline = res.unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    });
+
+    eprintln!(
+        "{} frames ({} collapsed), {:.3}s, max drift {:.3}ms, view travel {:.0} degrees",
+        timeline.frames(),
+        timeline.collapsed(),
+        timeline.elapsed(),
+        timeline.max_drift() * 1000.,
+        timeline.travel()
     );
+}
 
-    let mut dot_count = 0;
-
-    for x in 0..detection.width() {
-        for y in 0..detection.height() {
-            let edge = detection.interpolate(x as f32, y as f32);
-            let magnitude = edge.magnitude();
-
-            if dot_count >= MAX_DOTS && COUNT_DOTS {
-                break;
-            }
-
-            if magnitude > 0. {
-                res.push(image_coordinate_to_viewangles(
-                    (detection.width() as u32, detection.height() as u32),
-                    x as u32,
-                    y as u32,
-                ));
+fn check(files: &[PathBuf], round_trip: bool) {
+    let mut failed = false;
 
-                dot_count += 1;
+    for path in files {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                failed = true;
+                continue;
             }
-        }
-    }
-}
+        };
 
-fn dithering(img: DynamicImage, res: &mut Views) {
-    let mut my_image = img.into_luma8();
-    let dimensions = my_image.dimensions();
+        let res = if round_trip {
+            Hltas::parse_round_trip(&text)
+        } else {
+            Hltas::parse(&text)
+        };
 
-    image::imageops::dither(&mut my_image, &BiLevel);
+        match res {
+            Ok(hltas) => {
+                let frame_bulks = hltas.lines.iter().filter_map(|line| match line {
+                    Line::FrameBulk(frame_bulk) => Some(frame_bulk),
+                    _ => None,
+                });
 
-    for x in 0..dimensions.0 {
-        for y in 0..dimensions.1 {
-            let pixel = my_image.get_pixel(x, y);
-            if pixel.0[0] > 128 {
-                res.push(image_coordinate_to_viewangles(dimensions, x, y));
+                let (count, frames, duration) =
+                    frame_bulks.fold((0, 0, 0.), |(count, frames, duration), frame_bulk| {
+                        (
+                            count + 1,
+                            frames + frame_bulk.repeats as u64,
+                            duration + frame_bulk.frametime * frame_bulk.repeats as f64,
+                        )
+                    });
+
+                println!(
+                    "{}: {} frame lines, {} frames, {:.3}s",
+                    path.display(),
+                    count,
+                    frames,
+                    duration
+                );
+            }
+            Err(errors) => {
+                failed = true;
+
+                for error in errors.iter().take(MAX_REPORTED_ERRORS) {
+                    eprintln!("{}: {}", path.display(), error);
+                }
+
+                if errors.len() > MAX_REPORTED_ERRORS {
+                    eprintln!(
+                        "{}: {} more errors",
+                        path.display(),
+                        errors.len() - MAX_REPORTED_ERRORS
+                    );
+                }
             }
         }
     }
-}
-
-fn bilevel(img: DynamicImage, res: &mut Views) {
-    let my_image = img.into_luma8();
-    let dimensions = my_image.dimensions();
 
-    for x in 0..dimensions.0 {
-        for y in 0..dimensions.1 {
-            let pixel = my_image.get_pixel(x, y);
-            if pixel.0[0] > 128 {
-                res.push(image_coordinate_to_viewangles(dimensions, x, y));
-            }
-        }
+    if failed {
+        std::process::exit(1);
     }
 }
 
-fn image_coordinate_to_viewangles(dimensions: (u32, u32), x: u32, y: u32) -> [f32; 2] {
-    let center_x = dimensions.0 / 2;
-    let center_y = dimensions.1 / 2;
-
-    let diff_x = x as i32 - center_x as i32;
-    let diff_y = y as i32 - center_y as i32;
-
-    // pitch is y
-    // flip the pitch
-    let pitch = STARTING_PITCH
-        - diff_y as f32 / dimensions.1 as f32 * SCREEN_HEIGHT as f32 * ANGLE_PER_PIXEL;
-    let yaw =
-        diff_x as f32 / dimensions.0 as f32 * SCREEN_WIDTH as f32 * ANGLE_PER_PIXEL + STARTING_YAW;
+fn preview(
+    input: &Path,
+    preview: Preview,
+    out_dir: Option<&Path>,
+    gif: Option<&Path>,
+) -> Result<usize, String> {
+    if let Some(out_dir) = out_dir {
+        std::fs::create_dir_all(out_dir)
+            .map_err(|err| format!("cannot create {}: {}", out_dir.display(), err))?;
+    }
 
-    [pitch, yaw]
-}
+    let mut gif_encoder = gif
+        .map(|path| {
+            File::create(path)
+                .map_err(|err| format!("cannot create {}: {}", path.display(), err))
+                .map(|file| {
+                    let mut encoder = GifEncoder::new(BufWriter::new(file));
+                    encoder.set_repeat(Repeat::Infinite).ok();
+                    encoder
+                })
+        })
+        .transpose()?;
 
-enum Clear {
-    None,
-    Yes,
-    No,
-}
+    let mut count = 0;
+    let mut error = None;
 
-fn hltas_change_view_frame(pitch: f32, yaw: f32, should_clear: Clear) -> String {
-    format!(
-        "----------|------|------|{ZERO_MS_FRAMETIME}|{}|{}|1|{}",
-        yaw,
-        pitch,
-        match should_clear {
-            Clear::None => "",
-            Clear::Yes => "bxt_force_clear 1; gl_clear 1; sv_zmax 1",
-            Clear::No => "bxt_force_clear 0; gl_clear 0; sv_zmax 8192",
+    preview.run(input, |frame| {
+        if error.is_some() {
+            return;
         }
-    )
-}
 
-fn hltas_delay_frame() -> String {
-    format!("----------|------|------|{HLTAS_FRAMETIME}|{STARTING_YAW}|{STARTING_PITCH}|1")
-}
+        if let Some(out_dir) = out_dir {
+            let path = out_dir.join(format!("{count:05}.png"));
 
-fn frame_views_to_hltas(views: Views) -> String {
-    if views.is_empty() {
-        return "".to_string();
-    }
-
-    let mut res = String::new();
-
-    for (idx, view) in views.iter().enumerate() {
-        res += hltas_change_view_frame(
-            view[0],
-            view[1],
-            if idx == 0 {
-                Clear::Yes
-            } else if idx == 1 {
-                Clear::No
-            } else {
-                Clear::None
-            },
-        )
-        .as_str();
-        res += "\n";
-
-        if SLOW_DRAW {
-            res += format!("----------|------|------|{}|-|-|1|", SLOW_WAIT).as_str();
-            res += "\n";
+            if let Err(err) = frame.image.save(&path) {
+                error = Some(format!("cannot write {}: {}", path.display(), err));
+            }
         }
-    }
 
-    res += hltas_delay_frame().as_str();
-    res += "\n";
+        if let Some(encoder) = gif_encoder.as_mut() {
+            let delay = Delay::from_numer_denom_ms((frame.duration * 1000.).round() as u32, 1);
+            let rgba = DynamicImage::ImageLuma8(frame.image).into_rgba8();
 
-    res
-}
+            if let Err(err) = encoder.encode_frame(Frame::from_parts(rgba, 0, 0, delay)) {
+                error = Some(format!("cannot write gif: {err}"));
+            }
+        }
 
-fn hltas_template(hltas: String, next_frame: Option<u32>) -> String {
-    // need to have at least 2 frames in a hltas
-    let mut res = format!(
-        "\
-version 1
-hlstrafe_version 5
-load_command bxt_anglespeed_cap 0; gl_clear 1; bxt_force_clear 1; sv_zmax 1;
-frametime0ms {ZERO_MS_FRAMETIME}
-frames
-strafing vectorial
-target_yaw velocity_lock
-
-----------|------|------|{ZERO_MS_FRAMETIME}|0|-|1
-{hltas}
-"
-    );
+        count += 1;
+    })?;
 
-    if let Some(next_frame) = next_frame {
-        res += format!(
-            "\
-----------|------|------|{ZERO_MS_FRAMETIME}|0|-|1|echo \"frame {}\"; bxt_tas_loadscript out/{}.hltas",
-            next_frame,
-            next_frame
-        )
-        .as_str();
+    match error {
+        Some(error) => Err(error),
+        None => Ok(count),
     }
-
-    res
 }
 
 fn main() {
-    let file_path = Path::new(VIDEO_PATH);
-    let dimensions = VIDEO_DIMENSION;
+    let cli = Cli::parse();
 
-    let frame_source = FileSource::new(file_path, dimensions).unwrap();
+    match &cli.command {
+        Command::Video { input, output, .. } => {
+            let settings = settings(&cli);
+            let source = VideoSource::new(input, settings.video_dimension)
+                .unwrap_or_else(|err| Cli::command().error(ErrorKind::Io, err).exit());
 
-    let my_iter = frame_source.into_iter();
-    let iter_again = my_iter.enumerate();
+            convert(settings, source, input, output.as_deref());
+        }
+        Command::Sequence { input, output, .. } => {
+            let settings = settings(&cli);
+            let source = SequenceSource::new(input)
+                .unwrap_or_else(|err| Cli::command().error(ErrorKind::Io, err).exit());
 
-    let mut hltas_res = String::new();
+            convert(settings, source, input, output.as_deref());
+        }
+        Command::Animation { input, output, .. } => {
+            let settings = settings(&cli);
+            let source = AnimationSource::new(input)
+                .unwrap_or_else(|err| Cli::command().error(ErrorKind::Io, err).exit());
 
-    let separate_out_folder = file_path.with_file_name("out");
+            convert(settings, source, input, output.as_deref());
+        }
+        Command::Image { input, output, .. } => {
+            let settings = settings(&cli);
+            warn_timing(&settings);
 
-    if SEPARATE_HLTAS {
-        match std::fs::create_dir(separate_out_folder.as_path()) {
-            Ok(_) => (),
-            Err(err) => match err.kind() {
-                std::io::ErrorKind::AlreadyExists => (),
-                _ => panic!("cannot create `out` dir for hltas: {}", err),
-            },
-        };
-    }
+            let pipeline = Pipeline::new(settings);
+            let mut source = ImageSource::new(input)
+                .unwrap_or_else(|err| Cli::command().error(ErrorKind::Io, err).exit());
 
-    // video conversion
-    let mut count = 0;
-    let max = 1500;
-    for (index, frame) in iter_again {
-        if let Ok(Some(png_img_data)) = frame {
-            let cursor = Cursor::new(png_img_data);
-            let image = image::io::Reader::new(cursor)
-                .with_guessed_format()
-                .unwrap()
-                .decode()
-                .unwrap();
-
-            let image = resize_image(image);
-            let frame_res = process_frame(image);
-            let hltas_frame_res = frame_views_to_hltas(frame_res);
-
-            if count >= max {
-                break;
+            if let Err(err) = convert_to_single(&pipeline, &mut source, output.as_deref()) {
+                eprintln!("{err}");
+                std::process::exit(1);
             }
-
-            if SEPARATE_HLTAS {
-                let local_count = count;
-                let local_separtate_folder = separate_out_folder.clone();
-
-                let _handle = thread::spawn(move || {
-                    let res = hltas_template(hltas_frame_res, Some(local_count as u32 + 1));
-                    let mut file = OpenOptions::new()
-                        .create(true)
-                        .truncate(true)
-                        .write(true)
-                        .open(
-                            local_separtate_folder
-                                .join(local_count.to_string())
-                                .with_extension("hltas"),
-                        )
-                        .expect("cannot create new hltas file in `out` folder");
-
-                    write!(file, "{}", res).expect("cannot write to new hltas file");
-                    file.flush().expect("cannot flush new hltas file");
-                });
-            } else {
-                hltas_res += hltas_frame_res.as_str();
+        }
+        Command::Check { files, round_trip } => check(files, *round_trip),
+        Command::Calibrate {
+            top_left,
+            bottom_right,
+            center,
+            hltas,
+            divisions,
+            step,
+            hold,
+            ..
+        } => {
+            let mut settings = settings(&cli);
+
+            let target = match (top_left, bottom_right, center) {
+                (Some(top_left), Some(bottom_right), _) => Target::Corners {
+                    top_left: *top_left,
+                    bottom_right: *bottom_right,
+                },
+                (_, _, Some(center)) => Target::Center {
+                    center: *center,
+                    fov: settings.projection.fov,
+                },
+                _ => unreachable!("checked by clap"),
+            };
+
+            settings.projection = calibrate::solve(target, &settings.projection)
+                .unwrap_or_else(|err| Cli::command().error(ErrorKind::ValueValidation, err).exit());
+
+            if let Some(path) = hltas {
+                let script = calibrate::calibration_script(&settings, *divisions, *step, *hold);
+
+                if let Err(err) = writer::write_atomic(path, script) {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
             }
 
-            count += 1;
+            print!("{}", settings.to_toml());
+        }
+        Command::Preview {
+            input,
+            out_dir,
+            gif,
+            width,
+            height,
+            dot_size,
+            min_display_frametime,
+            ..
+        } => {
+            let projection = settings(&cli).projection;
+            let preview_settings = Preview {
+                dimensions: (
+                    width.unwrap_or(projection.screen_width),
+                    height.unwrap_or(projection.screen_height),
+                ),
+                projection,
+                dot_size: *dot_size,
+                min_display_frametime: *min_display_frametime,
+            };
+
+            match preview(input, preview_settings, out_dir.as_deref(), gif.as_deref()) {
+                Ok(count) => eprintln!("{count} frames"),
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            }
         }
-    }
-
-    // single image conversion
-    // let image = image::open("/home/khang/apple/xdd.png").unwrap();
-    // let image = resize_image(image);
-    // let res = process_frame(image);
-    // let hltas_res = frame_views_to_hltas(res);
-
-    if !SEPARATE_HLTAS {
-        let res = hltas_template(hltas_res, None);
-
-        println!("{res}");
     }
 }
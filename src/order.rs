@@ -0,0 +1,139 @@
+//! Orders the dots of a frame so the view travels less between them.
+//!
+//! Renderers hand out dots column by column, so the view jumps back to the top after every
+//! column. Everything here works in image pixels, which is what the angles follow.
+
+use crate::{render::Dots, settings::DrawOrder};
+
+/// 2-opt passes over a tour before settling
+const TWO_OPT_PASSES: usize = 8;
+
+/// Puts `dots` in `order`, strokes keep the order they were traced in.
+pub fn reorder(dots: &mut Dots, order: DrawOrder) {
+    if !dots.strokes.is_empty() {
+        return;
+    }
+
+    match order {
+        DrawOrder::Scan => (),
+        DrawOrder::Serpentine => {
+            // every other column goes up
+            dots.points.sort_unstable_by_key(|&[x, y]| {
+                let y = if x % 2 == 0 { y as i64 } else { -(y as i64) };
+                (x, y)
+            });
+        }
+        DrawOrder::Morton => dots.points.sort_by_cached_key(|&[x, y]| morton(x, y)),
+        DrawOrder::Hilbert => {
+            let side = dots.dimensions.0.max(dots.dimensions.1).next_power_of_two();
+            dots.points
+                .sort_by_cached_key(|&[x, y]| hilbert(side, x, y));
+        }
+        DrawOrder::NearestNeighbour => dots.points = nearest_neighbour(dots),
+        DrawOrder::TwoOpt => {
+            dots.points = nearest_neighbour(dots);
+            two_opt(&mut dots.points);
+        }
+    }
+}
+
+/// Interleaves the bits of `x` and `y`.
+fn morton(x: u32, y: u32) -> u64 {
+    fn spread(value: u32) -> u64 {
+        let mut res = value as u64;
+        res = (res | (res << 16)) & 0x0000_ffff_0000_ffff;
+        res = (res | (res << 8)) & 0x00ff_00ff_00ff_00ff;
+        res = (res | (res << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+        res = (res | (res << 2)) & 0x3333_3333_3333_3333;
+        (res | (res << 1)) & 0x5555_5555_5555_5555
+    }
+
+    spread(x) | (spread(y) << 1)
+}
+
+/// Distance along the Hilbert curve filling a `side` by `side` square.
+fn hilbert(side: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut res = 0;
+    let mut s = side / 2;
+
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        res += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+
+        // rotate the quadrant so the curve stays connected
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        s /= 2;
+    }
+
+    res
+}
+
+fn distance([ax, ay]: [u32; 2], [bx, by]: [u32; 2]) -> f64 {
+    (ax as f64 - bx as f64).hypot(ay as f64 - by as f64)
+}
+
+/// Always goes to the closest dot left, starting from the center where the view rests.
+fn nearest_neighbour(dots: &Dots) -> Vec<[u32; 2]> {
+    let mut left = dots.points.clone();
+    let mut res = Vec::with_capacity(left.len());
+    let mut current = [dots.dimensions.0 / 2, dots.dimensions.1 / 2];
+
+    while !left.is_empty() {
+        let (idx, _) = left
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| distance(current, **a).total_cmp(&distance(current, **b)))
+            .expect("not empty");
+
+        current = left.swap_remove(idx);
+        res.push(current);
+    }
+
+    res
+}
+
+/// Reverses stretches of the path while that makes it shorter.
+fn two_opt(points: &mut [[u32; 2]]) {
+    for _ in 0..TWO_OPT_PASSES {
+        let mut improved = false;
+
+        for i in 0..points.len().saturating_sub(2) {
+            for j in i + 2..points.len() {
+                // the path is open, reversing up to the last point only changes one edge
+                let before = distance(points[i], points[i + 1])
+                    + points
+                        .get(j + 1)
+                        .map_or(0., |&next| distance(points[j], next));
+                let after = distance(points[i], points[j])
+                    + points
+                        .get(j + 1)
+                        .map_or(0., |&next| distance(points[i + 1], next));
+
+                if after < before - f64::EPSILON {
+                    points[i + 1..=j].reverse();
+                    improved = true;
+                }
+            }
+        }
+
+        if !improved {
+            break;
+        }
+    }
+}
+
+/// Angular distance between two `[pitch, yaw]` views, yaw wrapping around.
+pub fn angle_between([pitch_a, yaw_a]: [f32; 2], [pitch_b, yaw_b]: [f32; 2]) -> f64 {
+    let pitch = pitch_b as f64 - pitch_a as f64;
+    let yaw = (yaw_b as f64 - yaw_a as f64 + 180.).rem_euclid(360.) - 180.;
+
+    pitch.hypot(yaw)
+}
@@ -0,0 +1,557 @@
+use std::{
+    fmt::Display,
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use image::{imageops, DynamicImage, GenericImageView};
+use rayon::prelude::*;
+
+use crate::{
+    adaptive::{self, Smoothing},
+    collapse::Collapse,
+    compress::compress,
+    delta::Delta,
+    emit::{Chain, Emitter, HltasEmitter},
+    hltas::Line,
+    order,
+    projection::dots_to_views,
+    render::{renderer_from_settings, Dots, Renderer},
+    settings::Settings,
+    source::FrameSource,
+    timing::{Quantization, Timeline},
+    vector,
+    writer::ScriptWriter,
+};
+
+/// Frames decoded ahead per thread, more keeps threads busier but holds more images.
+const FRAMES_PER_THREAD: usize = 4;
+
+/// Resizes, renders, projects and emits frames.
+///
+/// Frames are decoded one after another, processed in parallel and timed in order.
+///
+/// Stages default to what [`Settings`] asks for and can be replaced with
+/// [`Pipeline::with_renderer`] and [`Pipeline::with_emitter`].
+pub struct Pipeline {
+    settings: Settings,
+    renderer: Box<dyn Renderer>,
+    emitter: Arc<dyn Emitter>,
+    quantization: Quantization,
+    /// Output frame the song starts with
+    audio_start: usize,
+}
+
+impl Pipeline {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            renderer: renderer_from_settings(&settings),
+            emitter: Arc::new(HltasEmitter::new(&settings)),
+            quantization: Quantization::new(&settings.timing),
+            audio_start: settings
+                .audio
+                .start
+                .to_frame_index(1. / settings.timing.frametime),
+            settings,
+        }
+    }
+
+    pub fn with_renderer(mut self, renderer: impl Renderer + 'static) -> Self {
+        self.renderer = Box::new(renderer);
+        self
+    }
+
+    pub fn with_emitter(mut self, emitter: impl Emitter + 'static) -> Self {
+        self.emitter = Arc::new(emitter);
+        self
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    fn resize_image(&self, img: &DynamicImage, scale: f64) -> DynamicImage {
+        let dimensions = img.dimensions();
+        let width = (dimensions.0 as f64 * scale) as u32;
+        let height = (dimensions.1 as f64 * scale) as u32;
+
+        // dithering panics on images thinner than two pixels
+        if width < 2 || height < 2 {
+            return img.resize_exact(width.max(2), height.max(2), imageops::FilterType::Nearest);
+        }
+
+        img.resize(width, height, imageops::FilterType::Nearest)
+    }
+
+    /// Dots of one frame scaled by `scale`.
+    fn render(&self, img: &DynamicImage, scale: f64) -> Dots {
+        self.renderer.render(self.resize_image(img, scale))
+    }
+
+    /// Lines drawing `dots` in the configured order, over the screen unless `clear` is set.
+    fn emit(&self, mut dots: Dots, clear: bool) -> Vec<Line> {
+        order::reorder(&mut dots, self.settings.draw_order);
+        let mut views = dots_to_views(&dots, &self.settings.projection);
+
+        if !dots.strokes.is_empty() {
+            let step = vector::stroke_step(
+                dots.dimensions,
+                &self.settings.projection,
+                self.settings.vector.stroke_step,
+            );
+            views = vector::sweep(&views, &dots.strokes, step);
+        }
+
+        if clear {
+            self.emitter.frame(&views)
+        } else {
+            self.emitter.frame_over(&views)
+        }
+    }
+
+    /// Script lines drawing one frame, without the delay keeping it on screen.
+    pub fn process_frame(&self, img: DynamicImage) -> Vec<Line> {
+        self.emit(self.render(&img, self.settings.scale_factor), true)
+    }
+
+    /// Dots of frames in order, with the scale picked per frame.
+    fn render_adaptive(&self, batch: Vec<DynamicImage>, smoothing: &mut Smoothing) -> Vec<Dots> {
+        let searched: Vec<(f64, Dots)> = batch
+            .par_iter()
+            .map(|image| {
+                adaptive::search(
+                    &self.settings.adaptive,
+                    self.settings.scale_factor,
+                    |scale| self.render(image, scale),
+                    |dots| {
+                        vector::view_count(
+                            dots,
+                            &self.settings.projection,
+                            self.settings.vector.stroke_step,
+                        )
+                    },
+                )
+            })
+            .collect();
+
+        // smoothing needs the previous frame, the searched dots are kept when it changes nothing
+        let planned: Vec<(f64, Option<Dots>)> = searched
+            .into_iter()
+            .map(|(ideal, dots)| {
+                let scale = smoothing.next(ideal);
+                (scale, (scale == ideal).then_some(dots))
+            })
+            .collect();
+
+        batch
+            .into_par_iter()
+            .zip(planned)
+            .map(|(image, (scale, dots))| dots.unwrap_or_else(|| self.render(&image, scale)))
+            .collect()
+    }
+
+    /// Timeline of a conversion, already advanced by the script around the first frame.
+    fn timeline(&self) -> Timeline {
+        let mut res = Timeline::new(self.settings.timing.frametime);
+        res.advance(self.script_overhead());
+        res
+    }
+
+    /// Seconds the lines [`Emitter::script`] adds around a frame take.
+    fn script_overhead(&self) -> f64 {
+        let chain = Chain {
+            frame: 0,
+            path: String::new(),
+        };
+
+        self.quantization
+            .duration(&self.emitter.script(vec![], Some(chain)).lines)
+    }
+
+    /// Lines running `command`, counted on `timeline`.
+    fn timed_command(&self, command: &str, timeline: &mut Timeline) -> Vec<Line> {
+        let res = self.emitter.command(command);
+        timeline.advance(self.quantization.duration(&res));
+        res
+    }
+
+    /// Echoes where the song should be when `frame` starts, for the start of a chained script.
+    fn resync_marker(&self, frame: usize) -> Vec<Line> {
+        if self.settings.audio.path.is_none()
+            || !self.settings.audio.resync
+            || frame <= self.audio_start
+        {
+            return vec![];
+        }
+
+        let position = (frame - self.audio_start) as f64 * self.settings.timing.frametime;
+        self.emitter
+            .command(&format!("echo \"audio {position:.3}s\""))
+    }
+
+    /// Processes every frame of `source` in parallel, handing the frames out in order, `None`
+    /// for frames repeating the one on screen.
+    ///
+    /// Only a batch of frames is decoded at a time so memory stays flat on long videos. Stops at
+    /// the first frame `source` cannot decode or the first error `on_frame` returns.
+    fn for_each_frame<E: From<String>>(
+        &self,
+        source: &mut dyn FrameSource,
+        mut on_frame: impl FnMut(Option<Frame>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let batch_size = rayon::current_num_threads() * FRAMES_PER_THREAD;
+        let mut count = 0;
+        let mut smoothing = Smoothing::new(&self.settings.adaptive);
+        let mut delta = Delta::new(&self.settings.delta);
+        let mut collapse = Collapse::new(&self.settings.collapse);
+
+        loop {
+            let mut batch = Vec::with_capacity(batch_size);
+
+            while batch.len() < batch_size {
+                if self.settings.max_frames.is_some_and(|max| count >= max) {
+                    break;
+                }
+
+                let Some(image) = source.next_frame() else {
+                    break;
+                };
+
+                batch.push(image?);
+                count += 1;
+            }
+
+            if batch.is_empty() {
+                break;
+            }
+
+            let dots = if self.settings.adaptive.enabled {
+                self.render_adaptive(batch, &mut smoothing)
+            } else {
+                batch
+                    .into_par_iter()
+                    .map(|image| self.render(&image, self.settings.scale_factor))
+                    .collect()
+            };
+
+            // what is on screen depends on the frames before
+            let planned: Vec<Option<(Dots, bool)>> = dots
+                .into_iter()
+                .map(|dots| (!collapse.is_repeat(&dots)).then(|| delta.next(dots)))
+                .collect();
+
+            let frames: Vec<Option<Frame>> = planned
+                .into_par_iter()
+                .map(|frame| {
+                    frame.map(|(dots, clear)| Frame {
+                        lines: self.emit(dots, clear),
+                        over: !clear,
+                    })
+                })
+                .collect();
+
+            frames.into_iter().try_for_each(&mut on_frame)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drawing lines of one frame, kept on screen until the frame should end.
+    ///
+    /// A repeated frame, `None`, only keeps the frame on screen longer.
+    fn timed_frame(&self, frame: Option<Frame>, timeline: &mut Timeline) -> Vec<Line> {
+        let mut res = vec![];
+
+        if timeline.frames() == 0 {
+            if let Some(command) = self.settings.capture.start() {
+                res.extend(self.timed_command(&command, timeline));
+            }
+        }
+
+        // right before the first dot so the song starts with the drawing
+        if timeline.frames() == self.audio_start {
+            if let Some(command) = self.settings.audio.play_command() {
+                res.extend(self.timed_command(&command, timeline));
+            }
+        }
+
+        if frame.is_none() {
+            timeline.collapse_frame();
+        }
+
+        // a frame drawn over the screen keeps what is on it even without new dots
+        let drawn = frame
+            .as_ref()
+            .is_none_or(|frame| frame.over || !frame.lines.is_empty());
+        let frame = frame.map(|frame| frame.lines).unwrap_or_default();
+        timeline.advance(self.quantization.duration(&frame));
+        res.extend(frame);
+
+        let delay = if self.settings.timing.drift_free {
+            timeline.remaining()
+        } else if !drawn {
+            // nothing drawn, nothing to wait for
+            0.
+        } else {
+            self.settings.timing.frametime
+        };
+
+        // rounding here is made up for by the next frame's delay
+        let delay = self.quantization.played(delay);
+
+        // drawing ran past the end of the frame, the next one catches up
+        if delay > 0. {
+            let delay = self.emitter.delay(delay);
+            timeline.advance(self.quantization.duration(&delay));
+            res.extend(delay);
+        }
+
+        timeline.follow_view(&res);
+        timeline.end_frame();
+        self.compress(res)
+    }
+
+    /// `lines` with runs of the same frame merged, when compressing.
+    fn compress(&self, lines: Vec<Line>) -> Vec<Line> {
+        if self.settings.output.compress {
+            compress(lines)
+        } else {
+            lines
+        }
+    }
+
+    /// Lines ending the conversion after the last frame.
+    fn finish(&self, timeline: &mut Timeline) -> Vec<Line> {
+        match self.settings.capture.stop() {
+            Some(command) if timeline.frames() > 0 => self.timed_command(&command, timeline),
+            _ => vec![],
+        }
+    }
+
+    /// Converts every frame into one script.
+    pub fn convert_to_string(
+        &self,
+        source: &mut dyn FrameSource,
+    ) -> Result<(String, Timeline), String> {
+        let mut res = vec![];
+        let timeline = match self.convert_to_writer(source, &mut res) {
+            Ok(timeline) => timeline,
+            Err(ConvertError::Frame(err)) => return Err(err),
+            Err(ConvertError::Write(err)) => panic!("writing to memory does not fail: {err}"),
+        };
+
+        Ok((
+            String::from_utf8(res).expect("scripts are written from strings"),
+            timeline,
+        ))
+    }
+
+    /// Converts every frame into one script written to `out` as the frames come in, so memory
+    /// stays flat however long the input is.
+    pub fn convert_to_writer(
+        &self,
+        source: &mut dyn FrameSource,
+        out: &mut dyn Write,
+    ) -> Result<Timeline, ConvertError> {
+        let mut timeline = self.timeline();
+
+        // the body comes last without a chain, everything before it is the header
+        write!(out, "{}", self.emitter.script(vec![], None))?;
+
+        let write_lines = |out: &mut dyn Write, lines: Vec<Line>| {
+            lines.iter().try_for_each(|line| writeln!(out, "{line}"))
+        };
+
+        self.for_each_frame(source, |frame| {
+            write_lines(out, self.timed_frame(frame, &mut timeline)).map_err(ConvertError::Write)
+        })?;
+
+        write_lines(out, self.finish(&mut timeline))?;
+        out.flush()?;
+
+        Ok(timeline)
+    }
+
+    /// Converts every frame into chained scripts in `folder`, each loading the next.
+    ///
+    /// Every script is on disk when this returns, the first error stops the conversion.
+    pub fn convert_to_folder(
+        &self,
+        source: &mut dyn FrameSource,
+        folder: &Path,
+    ) -> Result<Timeline, String> {
+        std::fs::create_dir_all(folder)
+            .map_err(|err| format!("cannot create {}: {}", folder.display(), err))?;
+
+        let output = &self.settings.output;
+        let writer = ScriptWriter::new();
+        let mut timeline = self.timeline();
+        let script_overhead = self.script_overhead();
+        let reserved = self.chunk_reserve();
+        // a script is only written once we know whether another one follows
+        let mut chunk = Chunk::new(0, reserved);
+
+        let res = self.for_each_frame(source, |frame| {
+            let index = timeline.frames();
+            // a repeat stays in the script of the frame it repeats
+            let repeat = frame.is_none();
+            let lines = self.timed_frame(frame, &mut timeline);
+            let (line_count, bytes) = (lines.len(), self.byte_count(&lines));
+
+            let fits = chunk.frames == 0
+                || ((repeat || chunk.frames < output.chunk_frames)
+                    && output
+                        .max_lines
+                        .is_none_or(|max| chunk.lines + line_count <= max)
+                    && output
+                        .max_bytes
+                        .is_none_or(|max| chunk.bytes + bytes <= max));
+
+            if !fits {
+                let next_index = chunk.index + 1;
+                let done = std::mem::replace(&mut chunk, Chunk::new(next_index, reserved));
+                let next = Chain {
+                    frame: index,
+                    path: output.load_path(chunk.index),
+                };
+
+                writer.write(
+                    folder.join(output.script_name(done.index)),
+                    self.emitter.script(self.compress(done.body), Some(next)),
+                )?;
+
+                // played before the frame but only known to be needed now, the next delay makes
+                // up for it
+                let marker = self.resync_marker(index);
+                timeline.advance(script_overhead + self.quantization.duration(&marker));
+                chunk.body.extend(marker);
+            }
+
+            chunk.body.extend(lines);
+            chunk.frames += 1;
+            chunk.lines += line_count;
+            chunk.bytes += bytes;
+
+            Ok(())
+        });
+
+        let res = res.and_then(|_| {
+            if chunk.frames == 0 {
+                return Ok(());
+            }
+
+            chunk.body.extend(self.finish(&mut timeline));
+            writer.write(
+                folder.join(output.script_name(chunk.index)),
+                self.emitter.script(self.compress(chunk.body), None),
+            )
+        });
+
+        // the writers are joined even when converting failed
+        let finished = writer.finish();
+        res.and(finished).map(|_| timeline)
+    }
+
+    /// Lines and bytes every chained script needs besides its frames, erring on the big side.
+    fn chunk_reserve(&self) -> (usize, usize) {
+        let chain = Chain {
+            frame: usize::MAX,
+            path: self.settings.output.load_path(usize::MAX),
+        };
+
+        let mut extra = self.resync_marker(usize::MAX);
+        if let Some(command) = self.settings.capture.stop() {
+            extra.extend(self.emitter.command(&command));
+        }
+
+        let script = self.emitter.script(extra, Some(chain)).to_string();
+        (script.lines().count(), script.len())
+    }
+
+    /// Bytes `lines` take in a script, only counted when the size of scripts is capped.
+    fn byte_count(&self, lines: &[Line]) -> usize {
+        if self.settings.output.max_bytes.is_none() {
+            return 0;
+        }
+
+        lines.iter().map(|line| line.to_string().len() + 1).sum()
+    }
+}
+
+/// Why converting into one script stopped.
+#[derive(Debug)]
+pub enum ConvertError {
+    /// A frame could not be decoded
+    Frame(String),
+    Write(io::Error),
+}
+
+impl ConvertError {
+    /// Message telling what went wrong, naming `output` when writing to it failed.
+    pub fn message(&self, output: impl Display) -> String {
+        match self {
+            ConvertError::Frame(err) => err.clone(),
+            ConvertError::Write(err) => format!("cannot write {output}: {err}"),
+        }
+    }
+}
+
+impl From<String> for ConvertError {
+    fn from(err: String) -> Self {
+        ConvertError::Frame(err)
+    }
+}
+
+impl From<io::Error> for ConvertError {
+    fn from(err: io::Error) -> Self {
+        ConvertError::Write(err)
+    }
+}
+
+/// Lines of one frame.
+struct Frame {
+    lines: Vec<Line>,
+    /// Whether the lines draw over the screen instead of clearing it first
+    over: bool,
+}
+
+/// Frames going into one chained script.
+struct Chunk {
+    index: usize,
+    body: Vec<Line>,
+    frames: usize,
+    lines: usize,
+    bytes: usize,
+}
+
+impl Chunk {
+    fn new(index: usize, (lines, bytes): (usize, usize)) -> Self {
+        Self {
+            index,
+            body: vec![],
+            frames: 0,
+            lines,
+            bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma};
+
+    use super::*;
+
+    #[test]
+    fn dithers_images_scaled_below_two_pixels() {
+        let mut settings = Settings::preset("bad-apple-dither").unwrap();
+        settings.scale_factor = 0.003;
+
+        let pipeline = Pipeline::new(settings);
+        let image = DynamicImage::ImageLuma8(GrayImage::from_pixel(640, 360, Luma([255])));
+
+        assert_eq!(pipeline.resize_image(&image, 0.003).dimensions(), (2, 2));
+        assert!(!pipeline.process_frame(image).is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! Plays scripts back without the game.
+//!
+//! Every rendered frame stamps a dot where the view angles point, where the white brush of the
+//! map shows unless `sv_zmax 1` clips it away, and the screen is only wiped while
+//! `bxt_force_clear` or `gl_clear` is on. Frames long enough to be seen are handed out as images.
+
+use std::path::{Path, PathBuf};
+
+use image::{GrayImage, Luma};
+
+use crate::{
+    hltas::{Hltas, Line, Property},
+    projection::viewangles_to_image_coordinate,
+    settings::ProjectionSettings,
+};
+
+/// A frame that stays on screen.
+pub struct PreviewFrame {
+    pub image: GrayImage,
+    /// Seconds it stays on screen
+    pub duration: f64,
+}
+
+pub struct Preview {
+    pub projection: ProjectionSettings,
+    /// Size of the preview images
+    pub dimensions: (u32, u32),
+    /// Side of the square drawn for every dot, in preview pixels
+    pub dot_size: u32,
+    /// Frames at least this long are shown, shorter ones only draw
+    pub min_display_frametime: f64,
+}
+
+#[derive(Default)]
+struct State {
+    pitch: f32,
+    yaw: f32,
+    force_clear: bool,
+    gl_clear: bool,
+    /// `sv_zmax` is too short to reach the brush
+    hide_brush: bool,
+    next_script: Option<PathBuf>,
+}
+
+impl State {
+    fn run_commands(&mut self, commands: &str) {
+        for command in commands.split(';') {
+            let mut args = command.split_whitespace();
+
+            match (args.next(), args.next()) {
+                (Some("bxt_force_clear"), Some(value)) => self.force_clear = value != "0",
+                (Some("gl_clear"), Some(value)) => self.gl_clear = value != "0",
+                (Some("sv_zmax"), Some(value)) => {
+                    self.hide_brush = value.parse::<f64>().is_ok_and(|zmax| zmax <= 1.)
+                }
+                (Some("bxt_tas_loadscript"), Some(path)) => {
+                    self.next_script = Some(PathBuf::from(path))
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Preview {
+    /// Plays `path` and every script it chains into with `bxt_tas_loadscript`.
+    ///
+    /// Chained scripts are looked up by file name next to the script loading them, the path in
+    /// the command is relative to the game directory which we do not have.
+    pub fn run(&self, path: &Path, mut on_frame: impl FnMut(PreviewFrame)) -> Result<(), String> {
+        let mut canvas = GrayImage::new(self.dimensions.0, self.dimensions.1);
+        let mut state = State::default();
+        let mut path = path.to_path_buf();
+
+        loop {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+            let hltas = Hltas::parse(&text)
+                .map_err(|errors| format!("cannot parse {}: {}", path.display(), errors[0]))?;
+
+            for property in &hltas.properties {
+                if let Property::LoadCommand(command) = property {
+                    state.run_commands(command);
+                }
+            }
+
+            for line in &hltas.lines {
+                let Line::FrameBulk(frame_bulk) = line else {
+                    continue;
+                };
+
+                if let Some(command) = &frame_bulk.console_command {
+                    state.run_commands(command);
+                }
+
+                if let Some(yaw) = frame_bulk.yaw {
+                    state.yaw = yaw;
+                }
+
+                if let Some(pitch) = frame_bulk.pitch {
+                    state.pitch = pitch;
+                }
+
+                if state.force_clear || state.gl_clear {
+                    canvas.pixels_mut().for_each(|pixel| *pixel = Luma([0]));
+                }
+
+                if !state.hide_brush {
+                    self.stamp(&mut canvas, state.pitch, state.yaw);
+                }
+
+                let duration = frame_bulk.frametime * frame_bulk.repeats as f64;
+
+                if duration >= self.min_display_frametime {
+                    on_frame(PreviewFrame {
+                        image: canvas.clone(),
+                        duration,
+                    });
+                }
+            }
+
+            let Some(next_script) = state.next_script.take() else {
+                break;
+            };
+
+            let next_path = next_script
+                .file_name()
+                .map(|file_name| path.with_file_name(file_name))
+                .filter(|next_path| next_path.exists());
+
+            match next_path {
+                Some(next_path) => path = next_path,
+                // the chain is done
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn stamp(&self, canvas: &mut GrayImage, pitch: f32, yaw: f32) {
+        let Some([x, y]) =
+            viewangles_to_image_coordinate(self.dimensions, [pitch, yaw], &self.projection)
+        else {
+            return;
+        };
+
+        let half = self.dot_size / 2;
+
+        for dx in 0..self.dot_size {
+            for dy in 0..self.dot_size {
+                let (px, py) = ((x + dx).saturating_sub(half), (y + dy).saturating_sub(half));
+
+                if px < canvas.width() && py < canvas.height() {
+                    canvas.put_pixel(px, py, Luma([255]));
+                }
+            }
+        }
+    }
+}
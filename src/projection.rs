@@ -0,0 +1,185 @@
+use crate::{
+    render::Dots,
+    settings::{ProjectionKind, ProjectionSettings},
+    Views,
+};
+
+/// Offset from the image center, in image widths and heights.
+fn normalized_offset(dimensions: (u32, u32), x: u32, y: u32) -> (f64, f64) {
+    let center_x = dimensions.0 / 2;
+    let center_y = dimensions.1 / 2;
+
+    let diff_x = x as i32 - center_x as i32;
+    let diff_y = y as i32 - center_y as i32;
+
+    (
+        diff_x as f64 / dimensions.0 as f64,
+        diff_y as f64 / dimensions.1 as f64,
+    )
+}
+
+pub fn image_coordinate_to_viewangles(
+    dimensions: (u32, u32),
+    x: u32,
+    y: u32,
+    projection: &ProjectionSettings,
+) -> [f32; 2] {
+    let (offset_x, offset_y) = normalized_offset(dimensions, x, y);
+
+    let [pitch, yaw] = match projection.kind {
+        ProjectionKind::Linear => {
+            // pitch is y
+            // flip the pitch
+            let pitch = projection.starting_pitch
+                - offset_y * projection.screen_height as f64 * projection.angle_per_pixel;
+            let yaw = offset_x * projection.screen_width as f64 * projection.angle_per_pixel
+                + projection.starting_yaw;
+
+            [pitch, yaw]
+        }
+        ProjectionKind::Perspective => {
+            let perspective = Perspective::new(projection);
+            perspective.offset_to_viewangles(offset_x, offset_y)
+        }
+    };
+
+    [pitch as f32, yaw as f32]
+}
+
+/// Inverse of [`image_coordinate_to_viewangles`], `None` when the angles land outside the image.
+pub fn viewangles_to_image_coordinate(
+    dimensions: (u32, u32),
+    viewangles: [f32; 2],
+    projection: &ProjectionSettings,
+) -> Option<[u32; 2]> {
+    let [pitch, yaw] = viewangles;
+
+    let (offset_x, offset_y) = match projection.kind {
+        ProjectionKind::Linear => {
+            // yaw wraps around
+            let yaw_diff = (yaw as f64 - projection.starting_yaw + 180.).rem_euclid(360.) - 180.;
+            let pitch_diff = projection.starting_pitch - pitch as f64;
+
+            (
+                yaw_diff / (projection.screen_width as f64 * projection.angle_per_pixel),
+                pitch_diff / (projection.screen_height as f64 * projection.angle_per_pixel),
+            )
+        }
+        ProjectionKind::Perspective => {
+            let perspective = Perspective::new(projection);
+            perspective.viewangles_to_offset(pitch as f64, yaw as f64)?
+        }
+    };
+
+    let x = ((dimensions.0 / 2) as f64 + offset_x * dimensions.0 as f64).round();
+    let y = ((dimensions.1 / 2) as f64 + offset_y * dimensions.1 as f64).round();
+
+    if x < 0. || y < 0. || x >= dimensions.0 as f64 || y >= dimensions.1 as f64 {
+        return None;
+    }
+
+    Some([x as u32, y as u32])
+}
+
+pub fn dots_to_views(dots: &Dots, projection: &ProjectionSettings) -> Views {
+    dots.points
+        .iter()
+        .map(|&[x, y]| image_coordinate_to_viewangles(dots.dimensions, x, y, projection))
+        .collect()
+}
+
+type Vec3 = [f64; 3];
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Camera looking at the starting angles, the image fills its screen.
+///
+/// Like the linear projection, moving right in the image turns towards bigger yaw and moving down
+/// turns towards smaller pitch.
+struct Perspective {
+    forward: Vec3,
+    left: Vec3,
+    up: Vec3,
+    /// Half width of the screen at distance 1
+    tan_half_x: f64,
+    /// Half height of the screen at distance 1
+    tan_half_y: f64,
+}
+
+impl Perspective {
+    fn new(projection: &ProjectionSettings) -> Self {
+        let aspect_ratio = projection
+            .aspect_ratio
+            .unwrap_or(projection.screen_width as f64 / projection.screen_height as f64);
+
+        let mut tan_half_x = (projection.fov.to_radians() / 2.).tan();
+
+        // GoldSrc treats fov as the 4:3 one and widens it for wider screens
+        if projection.fov_4_3_scaling && aspect_ratio > 4. / 3. {
+            tan_half_x *= aspect_ratio / (4. / 3.);
+        }
+
+        let tan_half_y = tan_half_x / aspect_ratio;
+
+        // pitch is positive looking down in game
+        let elevation = (-projection.starting_pitch).to_radians();
+        let yaw = projection.starting_yaw.to_radians();
+
+        Self {
+            forward: [
+                elevation.cos() * yaw.cos(),
+                elevation.cos() * yaw.sin(),
+                elevation.sin(),
+            ],
+            left: [-yaw.sin(), yaw.cos(), 0.],
+            up: [
+                -elevation.sin() * yaw.cos(),
+                -elevation.sin() * yaw.sin(),
+                elevation.cos(),
+            ],
+            tan_half_x,
+            tan_half_y,
+        }
+    }
+
+    fn offset_to_viewangles(&self, offset_x: f64, offset_y: f64) -> [f64; 2] {
+        // the image spans [-0.5, 0.5], the screen spans [-tan_half, tan_half]
+        let x = offset_x * 2. * self.tan_half_x;
+        let y = offset_y * 2. * self.tan_half_y;
+
+        let direction: Vec3 =
+            std::array::from_fn(|i| self.forward[i] + x * self.left[i] + y * self.up[i]);
+
+        let yaw = direction[1].atan2(direction[0]).to_degrees();
+        let elevation = direction[2]
+            .atan2(direction[0].hypot(direction[1]))
+            .to_degrees();
+
+        [-elevation, yaw]
+    }
+
+    fn viewangles_to_offset(&self, pitch: f64, yaw: f64) -> Option<(f64, f64)> {
+        let elevation = (-pitch).to_radians();
+        let yaw = yaw.to_radians();
+
+        let direction = [
+            elevation.cos() * yaw.cos(),
+            elevation.cos() * yaw.sin(),
+            elevation.sin(),
+        ];
+
+        let depth = dot(direction, self.forward);
+
+        // behind the camera
+        if depth <= 0. {
+            return None;
+        }
+
+        let x = dot(direction, self.left) / depth;
+        let y = dot(direction, self.up) / depth;
+
+        Some((x / (2. * self.tan_half_x), y / (2. * self.tan_half_y)))
+    }
+}
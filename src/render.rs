@@ -0,0 +1,321 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use image::{imageops::BiLevel as BiLevelColorMap, DynamicImage, GrayImage};
+
+use crate::{
+    settings::{DotSelection, Mode, ProjectionSettings, Settings},
+    vector::{self, Vector},
+};
+
+/// Image coordinates of every dot to draw, in drawing order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dots {
+    /// Dimensions of the image the points are in
+    pub dimensions: (u32, u32),
+    pub points: Vec<[u32; 2]>,
+    /// Where every stroke starts in `points`, a stroke is drawn as lines through its points.
+    /// Empty when every point is a dot of its own.
+    pub strokes: Vec<usize>,
+}
+
+/// Turns a (resized) frame into dots.
+pub trait Renderer: Send + Sync {
+    fn render(&self, img: DynamicImage) -> Dots;
+}
+
+/// Renderer matching [`Settings::mode`], capped when [`Settings::count_dots`] is on.
+pub fn renderer_from_settings(settings: &Settings) -> Box<dyn Renderer> {
+    let canny = CannyEdge {
+        sigma: settings.canny.sigma as f32,
+        strong_threshold: settings.canny.strong_threshold as f32,
+        weak_threshold: settings.canny.weak_threshold as f32,
+    };
+
+    let renderer: Box<dyn Renderer> = match settings.mode {
+        Mode::CannyEdge => Box::new(canny),
+        Mode::Dithering => Box::new(Dithering),
+        Mode::BiLevel => Box::new(BiLevel),
+        Mode::Vector => Box::new(Vector {
+            source: settings.vector.source,
+            canny,
+            tolerance: settings.vector.tolerance,
+            min_length: settings.vector.min_length,
+        }),
+    };
+
+    if !settings.count_dots {
+        return renderer;
+    }
+
+    Box::new(Budgeted {
+        inner: renderer,
+        max_dots: settings.max_dots,
+        selection: settings.dot_selection,
+        projection: settings.projection.clone(),
+        stroke_step: settings.vector.stroke_step,
+    })
+}
+
+/// Detects edges from the video
+pub struct CannyEdge {
+    pub sigma: f32,
+    pub strong_threshold: f32,
+    pub weak_threshold: f32,
+}
+
+impl Renderer for CannyEdge {
+    fn render(&self, img: DynamicImage) -> Dots {
+        let img: GrayImage = img.into_luma8();
+        let detection = edge_detection::canny(
+            img,
+            self.sigma,            // sigma
+            self.strong_threshold, // strong threshold
+            self.weak_threshold,   // weak threshold
+        );
+
+        let mut res = Dots {
+            dimensions: (detection.width() as u32, detection.height() as u32),
+            points: vec![],
+            strokes: vec![],
+        };
+
+        for x in 0..detection.width() {
+            for y in 0..detection.height() {
+                let edge = detection.interpolate(x as f32, y as f32);
+                let magnitude = edge.magnitude();
+
+                if magnitude > 0. {
+                    res.points.push([x as u32, y as u32]);
+                }
+            }
+        }
+
+        res
+    }
+}
+
+/// Dithers the video
+pub struct Dithering;
+
+impl Renderer for Dithering {
+    fn render(&self, img: DynamicImage) -> Dots {
+        let mut my_image = img.into_luma8();
+
+        image::imageops::dither(&mut my_image, &BiLevelColorMap);
+
+        white_pixels(&my_image)
+    }
+}
+
+/// Just black and white aka pre-processed video
+pub struct BiLevel;
+
+impl Renderer for BiLevel {
+    fn render(&self, img: DynamicImage) -> Dots {
+        white_pixels(&img.into_luma8())
+    }
+}
+
+fn white_pixels(img: &GrayImage) -> Dots {
+    let dimensions = img.dimensions();
+    let mut res = Dots {
+        dimensions,
+        points: vec![],
+        strokes: vec![],
+    };
+
+    for x in 0..dimensions.0 {
+        for y in 0..dimensions.1 {
+            let pixel = img.get_pixel(x, y);
+            if pixel.0[0] > 128 {
+                res.points.push([x, y]);
+            }
+        }
+    }
+
+    res
+}
+
+/// Keeps at most `max_dots` of the dots `inner` renders, picked by `selection` so a capped frame
+/// still shows the whole picture. Kept dots stay in drawing order.
+///
+/// Strokes are kept or dropped whole, longest first, and count as the views sweeping along them.
+pub struct Budgeted {
+    pub inner: Box<dyn Renderer>,
+    pub max_dots: usize,
+    pub selection: DotSelection,
+    /// How strokes are projected, for counting their views
+    pub projection: ProjectionSettings,
+    pub stroke_step: Option<f64>,
+}
+
+impl Renderer for Budgeted {
+    fn render(&self, img: DynamicImage) -> Dots {
+        // edges are measured on the frame itself so every mode can use them
+        let gray = (self.selection == DotSelection::EdgeMagnitude).then(|| img.to_luma8());
+        let mut res = self.inner.render(img);
+
+        // strokes sweep through more views than they have points
+        if !res.strokes.is_empty() {
+            let lengths = vector::swept_lengths(&res, &self.projection, self.stroke_step);
+            return longest_strokes(res, &lengths, self.max_dots);
+        }
+
+        if res.points.len() <= self.max_dots {
+            return res;
+        }
+
+        let mut keep = match self.selection {
+            DotSelection::EdgeMagnitude => by_edge_magnitude(
+                &res.points,
+                gray.as_ref().expect("converted above"),
+                self.max_dots,
+            ),
+            DotSelection::Uniform => uniform(&res, self.max_dots),
+            DotSelection::Stratified => stratified(&res.points, self.max_dots),
+        };
+
+        keep.sort_unstable();
+        res.points = keep.into_iter().map(|idx| res.points[idx]).collect();
+
+        res
+    }
+}
+
+/// Longest strokes of `dots` that fit in `max` views, in drawing order, `lengths` being how many
+/// views each stroke takes.
+fn longest_strokes(dots: Dots, lengths: &[usize], max: usize) -> Dots {
+    let ends = dots
+        .strokes
+        .iter()
+        .skip(1)
+        .copied()
+        .chain([dots.points.len()]);
+    let mut strokes: Vec<(usize, usize, usize)> = dots
+        .strokes
+        .iter()
+        .copied()
+        .zip(ends)
+        .zip(lengths)
+        .map(|((start, end), &length)| (start, end, length))
+        .collect();
+    strokes.sort_by_key(|&(.., length)| Reverse(length));
+
+    let mut count = 0;
+    strokes.retain(|&(.., length)| {
+        let fits = count + length <= max;
+        count += if fits { length } else { 0 };
+        fits
+    });
+    strokes.sort_unstable();
+
+    let mut res = Dots {
+        dimensions: dots.dimensions,
+        points: vec![],
+        strokes: vec![],
+    };
+
+    for (start, end, _) in strokes {
+        res.strokes.push(res.points.len());
+        res.points.extend_from_slice(&dots.points[start..end]);
+    }
+
+    res
+}
+
+/// Indices of the `max` dots on the strongest edges, ties keep drawing order.
+fn by_edge_magnitude(points: &[[u32; 2]], img: &GrayImage, max: usize) -> Vec<usize> {
+    let mut res: Vec<usize> = (0..points.len()).collect();
+    res.sort_by_key(|&idx| Reverse(sobel(img, points[idx])));
+    res.truncate(max);
+    res
+}
+
+/// Gradient magnitude around a pixel, edges of the image repeat outwards.
+fn sobel(img: &GrayImage, [x, y]: [u32; 2]) -> u32 {
+    let (width, height) = img.dimensions();
+    let at = |dx: i64, dy: i64| {
+        let px = (x as i64 + dx).clamp(0, width as i64 - 1) as u32;
+        let py = (y as i64 + dy).clamp(0, height as i64 - 1) as u32;
+        img.get_pixel(px, py).0[0] as i32
+    };
+
+    let gx = at(1, -1) + 2 * at(1, 0) + at(1, 1) - at(-1, -1) - 2 * at(-1, 0) - at(-1, 1);
+    let gy = at(-1, 1) + 2 * at(0, 1) + at(1, 1) - at(-1, -1) - 2 * at(0, -1) - at(1, -1);
+
+    gx.unsigned_abs() + gy.unsigned_abs()
+}
+
+/// Indices of `max` dots spread over the frame.
+///
+/// The frame is cut into about `max` square cells and cells give up their dot closest to the
+/// center in turns, evenly spaced cells first when a turn cannot fit every cell.
+fn uniform(dots: &Dots, max: usize) -> Vec<usize> {
+    let (width, height) = dots.dimensions;
+    let cell = ((width as f64 * height as f64 / max as f64).sqrt() as u32).max(1);
+
+    let mut cells: HashMap<[u32; 2], Vec<usize>> = HashMap::new();
+    for (idx, &[x, y]) in dots.points.iter().enumerate() {
+        cells.entry([y / cell, x / cell]).or_default().push(idx);
+    }
+
+    let mut cells: Vec<([u32; 2], Vec<usize>)> = cells.into_iter().collect();
+    cells.sort_unstable_by_key(|(key, _)| *key);
+
+    for ([row, column], points) in &mut cells {
+        let center = [
+            *column as f64 * cell as f64 + cell as f64 / 2.,
+            *row as f64 * cell as f64 + cell as f64 / 2.,
+        ];
+        let distance = |[x, y]: [u32; 2]| (x as f64 - center[0]).hypot(y as f64 - center[1]);
+
+        points.sort_by(|&a, &b| distance(dots.points[a]).total_cmp(&distance(dots.points[b])));
+    }
+
+    let mut res = Vec::with_capacity(max);
+
+    for turn in 0.. {
+        let candidates: Vec<usize> = cells
+            .iter()
+            .filter_map(|(_, points)| points.get(turn).copied())
+            .collect();
+        let need = max - res.len();
+
+        if candidates.len() <= need {
+            res.extend(candidates);
+        } else {
+            res.extend((0..need).map(|i| candidates[i * candidates.len() / need]));
+        }
+
+        if res.len() == max {
+            break;
+        }
+    }
+
+    res
+}
+
+/// Indices of one random dot out of every one of `max` equal runs of dots in drawing order.
+///
+/// Seeded from the dot count so the same frame always keeps the same dots.
+fn stratified(points: &[[u32; 2]], max: usize) -> Vec<usize> {
+    let mut state = points.len() as u64;
+
+    (0..max)
+        .map(|i| {
+            let start = i * points.len() / max;
+            let end = (i + 1) * points.len() / max;
+
+            start + (splitmix64(&mut state) % (end - start) as u64) as usize
+        })
+        .collect()
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+
+    let mut res = *state;
+    res = (res ^ (res >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    res = (res ^ (res >> 27)).wrapping_mul(0x94d049bb133111eb);
+    res ^ (res >> 31)
+}
@@ -0,0 +1,42 @@
+//! Dots left on screen by the frames drawn so far, shared by [`crate::delta`] and
+//! [`crate::collapse`].
+
+use std::collections::HashSet;
+
+use crate::render::Dots;
+
+/// Points of the dots on screen and the dimensions they were rendered at.
+#[derive(Debug, Clone, Default)]
+pub struct Screen {
+    /// `None` before the first frame
+    dimensions: Option<(u32, u32)>,
+    points: HashSet<[u32; 2]>,
+}
+
+impl Screen {
+    /// Points of `dots`, in the form they are compared with the screen.
+    pub fn points_of(dots: &Dots) -> HashSet<[u32; 2]> {
+        dots.points.iter().copied().collect()
+    }
+
+    pub fn points(&self) -> &HashSet<[u32; 2]> {
+        &self.points
+    }
+
+    /// Whether `dots` were rendered at the scale of the dots on screen, dots of another scale do
+    /// not line up with them.
+    pub fn lines_up(&self, dots: &Dots) -> bool {
+        self.dimensions == Some(dots.dimensions)
+    }
+
+    /// Clears the screen and puts `points` of an image of `dimensions` on it.
+    pub fn replace(&mut self, dimensions: (u32, u32), points: HashSet<[u32; 2]>) {
+        self.dimensions = Some(dimensions);
+        self.points = points;
+    }
+
+    /// Draws `point` over the screen, `false` when it was already there.
+    pub fn add(&mut self, point: [u32; 2]) -> bool {
+        self.points.insert(point)
+    }
+}
@@ -0,0 +1,875 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    /// Detects edges from the video
+    CannyEdge,
+    /// Dithers the video
+    Dithering,
+    /// Just black and white aka pre-processed video
+    BiLevel,
+    /// Traces outlines into lines swept by the view
+    Vector,
+}
+
+/// Pixels the vector mode traces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VectorSource {
+    /// Edges canny detects
+    CannyEdge,
+    /// Outlines of the white parts of a black and white video
+    BiLevel,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VectorSettings {
+    pub source: VectorSource,
+    /// Farthest in pixels a traced outline may stray from the lines drawing it
+    pub tolerance: f64,
+    /// Outlines with fewer pixels are dropped
+    pub min_length: usize,
+    /// Most degrees the view moves between two frames of a line, never less than one image pixel
+    /// and one image pixel when unset
+    pub stroke_step: Option<f64>,
+}
+
+/// Which dots are kept when a frame has more than `max_dots`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DotSelection {
+    /// Dots on the strongest edges of the frame
+    EdgeMagnitude,
+    /// Dots spread evenly over the frame
+    Uniform,
+    /// One random dot out of every equal run of dots
+    Stratified,
+}
+
+/// Scale picked per frame instead of `scale_factor`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdaptiveSettings {
+    pub enabled: bool,
+    /// Dot count every frame aims for
+    pub min_dots: usize,
+    pub max_dots: usize,
+    /// Scales the search stays within
+    pub min_scale: f64,
+    pub max_scale: f64,
+    /// How much the scale may grow or shrink from one frame to the next, 0.1 is 10%
+    pub max_change: f64,
+}
+
+/// Order the dots of a frame are drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DrawOrder {
+    /// As rendered, column by column
+    Scan,
+    /// Column by column, every other one upwards
+    Serpentine,
+    /// Along a Z-order curve
+    Morton,
+    /// Along a Hilbert curve
+    Hilbert,
+    /// Always the closest dot next
+    NearestNeighbour,
+    /// Nearest neighbour tour shortened with 2-opt, slow on big frames
+    TwoOpt,
+}
+
+/// Draws only the dots a frame adds to the previous one, clearing the screen now and then.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeltaSettings {
+    pub enabled: bool,
+    /// Redraws the whole frame once dots left over from earlier frames outnumber this share of
+    /// its dots, 0.2 is 20%
+    pub max_stale: f64,
+    /// Redraws the whole frame at least every this many frames
+    pub keyframe_interval: usize,
+}
+
+/// Keeps a frame on screen instead of redrawing it when the next frames look the same.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollapseSettings {
+    pub enabled: bool,
+    /// Share of dots a frame may add or drop and still count as a repeat, 0 only collapses
+    /// identical frames
+    pub max_difference: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CannySettings {
+    pub sigma: f64,
+    pub strong_threshold: f64,
+    pub weak_threshold: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProjectionKind {
+    /// Constant angle per pixel, stretches away from the crosshair
+    Linear,
+    /// Proper perspective from the fov, the image fills the screen
+    Perspective,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectionSettings {
+    pub kind: ProjectionKind,
+
+    // origin
+    pub starting_yaw: f64,
+    pub starting_pitch: f64,
+
+    /// Linear: size the image is stretched to in pixels. Perspective: game resolution.
+    pub screen_width: u32,
+    pub screen_height: u32,
+
+    // linear
+    pub angle_per_pixel: f64,
+
+    // perspective
+    /// Horizontal fov as given to `default_fov`
+    pub fov: f64,
+    /// Aspect ratio the game renders at, `screen_width / screen_height` when unset
+    pub aspect_ratio: Option<f64>,
+    /// Widens the fov past 4:3 like GoldSrc does on widescreen
+    pub fov_4_3_scaling: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimingSettings {
+    pub zero_ms_frametime: f64,
+    /// Frametime of the delay frame ending every video frame
+    pub frametime: f64,
+    /// Sizes every delay so frame N ends at N * frametime, instead of always using `frametime`
+    pub drift_free: bool,
+    /// Writes frametimes the engine can play, whole milliseconds or 0ms frames
+    pub quantize: bool,
+
+    // DRAW with some wait in between
+    pub slow_draw: bool,
+    pub slow_wait: f64,
+}
+
+/// Frame index, or a timestamp such as `12.5s` or `1:02.5`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum FramePosition {
+    Frame(usize),
+    Seconds(f64),
+}
+
+/// Which input frames are converted and how often.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputSettings {
+    /// Frame rate of the input, overrides what the input says
+    pub fps: Option<f64>,
+    /// First frame converted
+    pub start: Option<FramePosition>,
+    /// Frame the conversion stops before
+    pub end: Option<FramePosition>,
+    /// Takes every nth frame
+    pub stride: usize,
+    /// Drops or repeats frames to get this frame rate
+    pub output_fps: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AudioCommand {
+    /// `mp3 play`, path relative to the mod folder
+    Mp3,
+    /// `play`, path relative to `sound/`
+    Play,
+    /// `spk`, a sentence or a path relative to `sound/`
+    Spk,
+}
+
+/// Song started by the script.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioSettings {
+    /// Played with `command`, nothing is played when unset
+    pub path: Option<String>,
+    pub command: AudioCommand,
+    /// Output frame the song starts with
+    pub start: FramePosition,
+    /// Echoes where the song should be at the start of every chained script
+    pub resync: bool,
+}
+
+/// In game recording of the result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptureSettings {
+    /// Starts capturing with the first frame and stops after the last
+    pub enabled: bool,
+    /// Sets `bxt_cap_fps` before starting
+    pub fps: Option<f64>,
+    pub start_command: String,
+    pub stop_command: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputSettings {
+    /// Chained scripts with `bxt_tas_loadscript` instead of one big script
+    pub separate_hltas: bool,
+    /// Folder the chained scripts are written to, `out` next to the input when unset
+    pub dir: Option<PathBuf>,
+    /// Scripts are named `<prefix><number>.hltas`
+    pub prefix: String,
+    /// Zero pads script numbers to this many digits
+    pub padding: usize,
+    /// Folder the scripts are loaded from in game, relative to the mod folder
+    pub game_dir: String,
+    /// Video frames per script
+    pub chunk_frames: usize,
+    /// Starts a new script before one would get more lines than this
+    pub max_lines: Option<usize>,
+    /// Starts a new script before one would get bigger than this many bytes
+    pub max_bytes: Option<usize>,
+    /// Writes runs of the same frame as one line with a repeat count
+    pub compress: bool,
+}
+
+/// Everything a conversion needs to know.
+///
+/// Defaults are what used to be hard coded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // scaling image
+    pub scale_factor: f64,
+    // Change mode of image
+    pub mode: Mode,
+    pub adaptive: AdaptiveSettings,
+
+    pub canny: CannySettings,
+    pub vector: VectorSettings,
+    pub projection: ProjectionSettings,
+    pub timing: TimingSettings,
+
+    // Caps dot count on screen
+    pub count_dots: bool,
+    pub max_dots: usize,
+    /// Which dots are kept when capping
+    pub dot_selection: DotSelection,
+
+    /// Order the dots of a frame are drawn in
+    pub draw_order: DrawOrder,
+    /// Most degrees the view turns in one frame, bigger turns go through hidden views
+    pub max_angle_step: Option<f64>,
+    pub delta: DeltaSettings,
+    pub collapse: CollapseSettings,
+
+    pub video_dimension: (u32, u32),
+    /// Stops after this many frames
+    pub max_frames: Option<usize>,
+
+    pub input: InputSettings,
+    pub audio: AudioSettings,
+    pub capture: CaptureSettings,
+    pub output: OutputSettings,
+}
+
+impl Default for AdaptiveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_dots: 150,
+            max_dots: 300,
+            min_scale: 0.02,
+            max_scale: 0.5,
+            max_change: 0.1,
+        }
+    }
+}
+
+impl Default for DeltaSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_stale: 0.2,
+            keyframe_interval: 24,
+        }
+    }
+}
+
+impl Default for CollapseSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_difference: 0.,
+        }
+    }
+}
+
+impl Default for VectorSettings {
+    fn default() -> Self {
+        Self {
+            source: VectorSource::CannyEdge,
+            tolerance: 1.,
+            min_length: 3,
+            stroke_step: None,
+        }
+    }
+}
+
+impl Default for CannySettings {
+    fn default() -> Self {
+        Self {
+            sigma: 1.2,
+            strong_threshold: 0.2,
+            weak_threshold: 0.01,
+        }
+    }
+}
+
+impl Default for ProjectionSettings {
+    fn default() -> Self {
+        Self {
+            kind: ProjectionKind::Linear,
+            starting_yaw: 90.197754,
+            starting_pitch: -0.022000,
+            screen_width: 1280,
+            screen_height: 720,
+            angle_per_pixel: 0.0625 / 2.,
+            fov: 90.,
+            aspect_ratio: None,
+            fov_4_3_scaling: true,
+        }
+    }
+}
+
+impl Default for TimingSettings {
+    fn default() -> Self {
+        Self {
+            zero_ms_frametime: 0.0000000001,
+            frametime: 0.04171, // video is 23.97602fps
+            drift_free: true,
+            quantize: true,
+            slow_draw: true,
+            slow_wait: 0.000001,
+        }
+    }
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self {
+            fps: None,
+            start: None,
+            end: None,
+            stride: 1,
+            output_fps: None,
+        }
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            path: None,
+            command: AudioCommand::Mp3,
+            start: FramePosition::Frame(0),
+            resync: true,
+        }
+    }
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fps: None,
+            start_command: "bxt_cap_start".to_string(),
+            stop_command: "bxt_cap_stop".to_string(),
+        }
+    }
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            separate_hltas: true,
+            dir: None,
+            prefix: String::new(),
+            padding: 0,
+            game_dir: "out".to_string(),
+            chunk_frames: 1,
+            max_lines: None,
+            max_bytes: None,
+            compress: true,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            scale_factor: 0.125,
+            mode: Mode::Dithering,
+            adaptive: AdaptiveSettings::default(),
+            canny: CannySettings::default(),
+            vector: VectorSettings::default(),
+            projection: ProjectionSettings::default(),
+            timing: TimingSettings::default(),
+            count_dots: false,
+            max_dots: 240,
+            dot_selection: DotSelection::Uniform,
+            draw_order: DrawOrder::Scan,
+            max_angle_step: None,
+            delta: DeltaSettings::default(),
+            collapse: CollapseSettings::default(),
+            video_dimension: (1280, 720),
+            max_frames: None,
+            input: InputSettings::default(),
+            audio: AudioSettings::default(),
+            capture: CaptureSettings::default(),
+            output: OutputSettings::default(),
+        }
+    }
+}
+
+impl AudioSettings {
+    /// Console command starting the song, `None` when there is no song.
+    pub fn play_command(&self) -> Option<String> {
+        let command = match self.command {
+            AudioCommand::Mp3 => "mp3 play",
+            AudioCommand::Play => "play",
+            AudioCommand::Spk => "spk",
+        };
+
+        self.path.as_ref().map(|path| format!("{command} {path}"))
+    }
+}
+
+impl CaptureSettings {
+    /// Console command starting the capture, `None` when capturing is off.
+    pub fn start(&self) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+
+        Some(match self.fps {
+            Some(fps) => format!("bxt_cap_fps {fps}; {}", self.start_command),
+            None => self.start_command.clone(),
+        })
+    }
+
+    /// Console command stopping the capture, `None` when capturing is off.
+    pub fn stop(&self) -> Option<String> {
+        self.enabled.then(|| self.stop_command.clone())
+    }
+}
+
+impl OutputSettings {
+    /// File name of chained script number `index`.
+    pub fn script_name(&self, index: usize) -> String {
+        format!(
+            "{}{:0width$}.hltas",
+            self.prefix,
+            index,
+            width = self.padding
+        )
+    }
+
+    /// Path `bxt_tas_loadscript` loads chained script number `index` from.
+    pub fn load_path(&self, index: usize) -> String {
+        match self.game_dir.trim_end_matches('/') {
+            "" => self.script_name(index),
+            game_dir => format!("{game_dir}/{}", self.script_name(index)),
+        }
+    }
+}
+
+impl FramePosition {
+    pub fn to_frame_index(self, fps: f64) -> usize {
+        match self {
+            FramePosition::Frame(index) => index,
+            FramePosition::Seconds(seconds) => (seconds * fps).round() as usize,
+        }
+    }
+}
+
+impl FromStr for FramePosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error =
+            || format!("expected a frame index or a timestamp like `12.5s` or `1:02.5`, got `{s}`");
+
+        if let Ok(index) = s.parse() {
+            return Ok(Self::Frame(index));
+        }
+
+        let seconds = if let Some(seconds) = s.strip_suffix('s') {
+            seconds.parse::<f64>().map_err(|_| error())?
+        } else {
+            // [hours:]minutes:seconds
+            let mut res = 0.;
+
+            for (idx, part) in s.split(':').enumerate() {
+                if idx > 2 {
+                    return Err(error());
+                }
+
+                res = res * 60. + part.parse::<f64>().map_err(|_| error())?;
+            }
+
+            if !s.contains(':') {
+                return Err(error());
+            }
+
+            res
+        };
+
+        if !(seconds.is_finite() && seconds >= 0.) {
+            return Err(error());
+        }
+
+        Ok(Self::Seconds(seconds))
+    }
+}
+
+impl TryFrom<String> for FramePosition {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for FramePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FramePosition::Frame(index) => write!(f, "{index}"),
+            FramePosition::Seconds(seconds) => write!(f, "{seconds}s"),
+        }
+    }
+}
+
+impl From<FramePosition> for String {
+    fn from(value: FramePosition) -> Self {
+        value.to_string()
+    }
+}
+
+/// Built in presets, usable with `--preset` or `preset = "..."` in a config file.
+pub const PRESETS: &[&str] = &["bad-apple-dither", "canny-lowdot", "bilevel"];
+
+impl Settings {
+    pub fn preset(name: &str) -> Option<Self> {
+        let default = Self::default();
+
+        let res = match name {
+            // what the original bad apple run used
+            "bad-apple-dither" => default,
+            // outlines only, capped so every frame draws quickly
+            "canny-lowdot" => Self {
+                scale_factor: 0.25,
+                mode: Mode::CannyEdge,
+                count_dots: true,
+                max_dots: 240,
+                ..default
+            },
+            "bilevel" => Self {
+                mode: Mode::BiLevel,
+                ..default
+            },
+            _ => return None,
+        };
+
+        Some(res)
+    }
+
+    /// Reads a TOML or JSON (by extension) config file.
+    ///
+    /// Keys missing from the file come from `preset`, else from the preset named by the top
+    /// level `preset` key of the file, else from the defaults.
+    pub fn from_config_file(path: &Path, preset: Option<&str>) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read config {}: {}", path.display(), err))?;
+
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+        let mut file_value: serde_json::Value = if is_json {
+            serde_json::from_str(&text).map_err(|err| err.to_string())
+        } else {
+            toml::from_str(&text).map_err(|err| err.to_string())
+        }
+        .map_err(|err| format!("cannot parse config {}: {}", path.display(), err))?;
+
+        let file_preset = match file_value
+            .as_object_mut()
+            .and_then(|table| table.remove("preset"))
+        {
+            Some(serde_json::Value::String(name)) => Some(name),
+            Some(other) => return Err(format!("preset must be a string, got {other}")),
+            None => None,
+        };
+
+        let base = match preset.or(file_preset.as_deref()) {
+            Some(name) => Self::preset(name).ok_or_else(|| format!("unknown preset `{name}`"))?,
+            None => Self::default(),
+        };
+
+        let mut value = serde_json::to_value(base).expect("settings are always serializable");
+        merge(&mut value, file_value);
+
+        serde_json::from_value(value)
+            .map_err(|err| format!("invalid config {}: {}", path.display(), err))
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("settings are always serializable")
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("settings are always serializable")
+    }
+}
+
+/// Deep merges tables so a config only needs the keys it changes.
+fn merge(base: &mut serde_json::Value, other: serde_json::Value) {
+    match (base, other) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(other)) => {
+            for (key, value) in other {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, other) => *base = other,
+    }
+}
+
+fn check_positive(name: &str, value: f64) -> Result<(), String> {
+    if value.is_finite() && value > 0. {
+        Ok(())
+    } else {
+        Err(format!("{name} must be positive, got {value}"))
+    }
+}
+
+impl Settings {
+    /// Rejects values that would panic or produce a broken script.
+    pub fn validate(&self) -> Result<(), String> {
+        check_positive("scale factor", self.scale_factor)?;
+
+        let adaptive = &self.adaptive;
+
+        if adaptive.min_dots == 0 || adaptive.min_dots > adaptive.max_dots {
+            return Err(format!(
+                "adaptive dot range must be non zero and in order, got {} to {}",
+                adaptive.min_dots, adaptive.max_dots
+            ));
+        }
+
+        check_positive("adaptive min scale", adaptive.min_scale)?;
+
+        if adaptive.min_scale > adaptive.max_scale {
+            return Err(format!(
+                "adaptive min scale {} is bigger than max scale {}",
+                adaptive.min_scale, adaptive.max_scale
+            ));
+        }
+
+        check_positive("adaptive max change", adaptive.max_change)?;
+
+        let canny = &self.canny;
+
+        check_positive("sigma", canny.sigma)?;
+
+        // edge_detection asserts these
+        if !(0. < canny.weak_threshold
+            && canny.weak_threshold < canny.strong_threshold
+            && canny.strong_threshold < 1.)
+        {
+            return Err(format!(
+                "canny thresholds must satisfy 0 < weak < strong < 1, got weak {} and strong {}",
+                canny.weak_threshold, canny.strong_threshold
+            ));
+        }
+
+        check_positive("tolerance", self.vector.tolerance)?;
+        if let Some(stroke_step) = self.vector.stroke_step {
+            check_positive("stroke step", stroke_step)?;
+        }
+
+        let projection = &self.projection;
+
+        if projection.screen_width == 0 || projection.screen_height == 0 {
+            return Err("screen dimension must not be zero".to_string());
+        }
+
+        check_positive("angle per pixel", projection.angle_per_pixel)?;
+
+        if !(projection.fov > 0. && projection.fov < 180.) {
+            return Err(format!(
+                "fov must be between 0 and 180, got {}",
+                projection.fov
+            ));
+        }
+
+        if let Some(aspect_ratio) = projection.aspect_ratio {
+            check_positive("aspect ratio", aspect_ratio)?;
+        }
+
+        let timing = &self.timing;
+
+        check_positive("zero ms frametime", timing.zero_ms_frametime)?;
+        check_positive("frametime", timing.frametime)?;
+        check_positive("slow wait", timing.slow_wait)?;
+
+        if self.max_dots == 0 {
+            return Err("max dots must not be zero".to_string());
+        }
+
+        if let Some(max_angle_step) = self.max_angle_step {
+            check_positive("max angle step", max_angle_step)?;
+        }
+
+        let delta = &self.delta;
+
+        if !(delta.max_stale.is_finite() && delta.max_stale >= 0.) {
+            return Err(format!(
+                "delta max stale must not be negative, got {}",
+                delta.max_stale
+            ));
+        }
+
+        if delta.keyframe_interval == 0 {
+            return Err("keyframe interval must not be zero".to_string());
+        }
+
+        let max_difference = self.collapse.max_difference;
+
+        if !(0. ..1.).contains(&max_difference) {
+            return Err(format!(
+                "collapse max difference must be at least 0 and under 1, got {max_difference}"
+            ));
+        }
+
+        let input = &self.input;
+
+        if let Some(fps) = input.fps {
+            check_positive("fps", fps)?;
+        }
+
+        if let Some(output_fps) = input.output_fps {
+            check_positive("output fps", output_fps)?;
+        }
+
+        if input.stride == 0 {
+            return Err("stride must not be zero".to_string());
+        }
+
+        if let Some(path) = &self.audio.path {
+            if path.is_empty() || path.contains([';', '"', '\n']) {
+                return Err(format!(
+                    "audio path must be non empty without `;`, `\"` or newlines, got `{path}`"
+                ));
+            }
+        }
+
+        let capture = &self.capture;
+
+        if let Some(fps) = capture.fps {
+            check_positive("capture fps", fps)?;
+        }
+
+        for command in [&capture.start_command, &capture.stop_command] {
+            if command.trim().is_empty() || command.contains('\n') {
+                return Err(format!(
+                    "capture commands must be non empty without newlines, got `{command}`"
+                ));
+            }
+        }
+
+        let output = &self.output;
+
+        // both end up in a console command
+        for (name, value) in [("prefix", &output.prefix), ("game dir", &output.game_dir)] {
+            if value.contains(|c: char| c.is_whitespace() || c == ';' || c == '"') {
+                return Err(format!(
+                    "{name} must not contain whitespace, `;` or `\"`, got `{value}`"
+                ));
+            }
+        }
+
+        if output.prefix.contains(['/', '\\']) {
+            return Err(format!(
+                "prefix must not contain path separators, got `{}`",
+                output.prefix
+            ));
+        }
+
+        if output.chunk_frames == 0 {
+            return Err("chunk frames must not be zero".to_string());
+        }
+
+        if output.max_lines == Some(0) || output.max_bytes == Some(0) {
+            return Err("max lines and max bytes must not be zero".to_string());
+        }
+
+        if self.video_dimension.0 == 0 || self.video_dimension.1 == 0 {
+            return Err("video dimension must not be zero".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_frame_positions() {
+        assert_eq!("123".parse(), Ok(FramePosition::Frame(123)));
+        assert_eq!("1.5s".parse(), Ok(FramePosition::Seconds(1.5)));
+        assert_eq!("00:01.5".parse(), Ok(FramePosition::Seconds(1.5)));
+        assert_eq!("1:02:03".parse(), Ok(FramePosition::Seconds(3723.)));
+    }
+
+    #[test]
+    fn rejects_bad_frame_positions() {
+        for position in ["", "1.5", "-1s", "abc", "1:xs", "1:2:3:4", "infs"] {
+            assert!(
+                position.parse::<FramePosition>().is_err(),
+                "{position:?} parsed"
+            );
+        }
+    }
+
+    #[test]
+    fn converts_timestamps_to_frames() {
+        assert_eq!(FramePosition::Frame(7).to_frame_index(30.), 7);
+        assert_eq!(FramePosition::Seconds(1.5).to_frame_index(30.), 45);
+    }
+}
@@ -0,0 +1,590 @@
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder},
+    AnimationDecoder, DynamicImage, Frames, ImageFormat,
+};
+
+use crate::settings::InputSettings;
+
+/// Where frames come from.
+pub trait FrameSource {
+    /// Next decoded frame or why it could not be decoded, `None` once there are no more.
+    fn next_frame(&mut self) -> Option<Result<DynamicImage, String>>;
+
+    /// Frames per second, `None` when the source does not know.
+    fn fps(&self) -> Option<f64> {
+        None
+    }
+}
+
+/// Frames of a video file, decoded by gstreamer.
+pub struct VideoSource {
+    frames: vid2img::VideoStreamIterator,
+}
+
+impl VideoSource {
+    pub fn new(path: &Path, dimensions: (u32, u32)) -> Result<Self, String> {
+        let frame_source = vid2img::FileSource::new(path, dimensions)
+            .map_err(|err| format!("cannot open video {}: {}", path.display(), err))?;
+
+        Ok(Self {
+            frames: frame_source.into_iter(),
+        })
+    }
+}
+
+impl FrameSource for VideoSource {
+    fn next_frame(&mut self) -> Option<Result<DynamicImage, String>> {
+        for frame in self.frames.by_ref() {
+            // skips frames gstreamer could not capture
+            if let Ok(Some(png_img_data)) = frame {
+                let cursor = Cursor::new(png_img_data);
+                let image = image::io::Reader::new(cursor)
+                    .with_guessed_format()
+                    .expect("reading from memory does not fail")
+                    .decode()
+                    .map_err(|err| format!("cannot decode video frame: {err}"));
+
+                return Some(image);
+            }
+        }
+
+        None
+    }
+}
+
+/// A single still image, one frame.
+pub struct ImageSource {
+    image: Option<DynamicImage>,
+}
+
+impl ImageSource {
+    pub fn new(path: &Path) -> Result<Self, String> {
+        let image = image::open(path)
+            .map_err(|err| format!("cannot open image {}: {}", path.display(), err))?;
+
+        Ok(Self { image: Some(image) })
+    }
+}
+
+impl From<DynamicImage> for ImageSource {
+    fn from(image: DynamicImage) -> Self {
+        Self { image: Some(image) }
+    }
+}
+
+impl FrameSource for ImageSource {
+    fn next_frame(&mut self) -> Option<Result<DynamicImage, String>> {
+        self.image.take().map(Ok)
+    }
+}
+
+/// Numbered frames in a folder, in number order.
+pub struct SequenceSource {
+    paths: std::vec::IntoIter<PathBuf>,
+}
+
+impl SequenceSource {
+    /// `pattern` is either a folder, taking every PNG and JPEG inside as long as they are all
+    /// named alike, or a path whose file name has `*` and `?` wildcards such as
+    /// `frames/frame_*.png`.
+    pub fn new(pattern: &Path) -> Result<Self, String> {
+        let (folder, file_pattern) = if pattern.is_dir() {
+            (pattern, None)
+        } else {
+            let folder = pattern
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            let file_pattern = pattern
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .ok_or_else(|| format!("invalid pattern {}", pattern.display()))?;
+
+            (folder, Some(file_pattern))
+        };
+
+        let entries = std::fs::read_dir(folder)
+            .map_err(|err| format!("cannot read {}: {}", folder.display(), err))?;
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .filter(|path| {
+                let Some(file_name) = path.file_name().and_then(|file_name| file_name.to_str())
+                else {
+                    return false;
+                };
+
+                match file_pattern {
+                    Some(file_pattern) => wildcard_match(file_pattern, file_name),
+                    None => path
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| {
+                            ["png", "jpg", "jpeg"].contains(&ext.to_ascii_lowercase().as_str())
+                        }),
+                }
+            })
+            .collect();
+
+        if paths.is_empty() {
+            return Err(format!("no frames match {}", pattern.display()));
+        }
+
+        // frames of two sequences would be shuffled together by number
+        if file_pattern.is_none() {
+            let mut patterns: Vec<String> = paths
+                .iter()
+                .map(|path| {
+                    numbered_pattern(&path.file_name().unwrap_or_default().to_string_lossy())
+                })
+                .collect();
+            patterns.sort_unstable();
+            patterns.dedup();
+
+            if patterns.len() > 1 {
+                return Err(format!(
+                    "{} holds frames named like {}, pick one with a pattern such as {}",
+                    folder.display(),
+                    patterns.join(", "),
+                    folder.join(&patterns[0]).display()
+                ));
+            }
+        }
+
+        paths.sort_by_cached_key(|path| frame_number_key(path));
+
+        Ok(Self {
+            paths: paths.into_iter(),
+        })
+    }
+}
+
+impl FrameSource for SequenceSource {
+    fn next_frame(&mut self) -> Option<Result<DynamicImage, String>> {
+        let path = self.paths.next()?;
+        let image = image::open(&path)
+            .map_err(|err| format!("cannot open frame {}: {}", path.display(), err));
+
+        Some(image)
+    }
+}
+
+/// `*` matches any run of characters, `?` any one character.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // last `*` seen and where in `text` it is matching up to
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // let the `*` eat one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// `file_name` with its frame number, the last run of digits, as a `*` wildcard.
+fn numbered_pattern(file_name: &str) -> String {
+    let Some(end) = file_name.rfind(|c: char| c.is_ascii_digit()) else {
+        return file_name.to_string();
+    };
+
+    let (head, tail) = file_name.split_at(end + 1);
+    let prefix = head.trim_end_matches(|c: char| c.is_ascii_digit());
+
+    format!("{prefix}*{tail}")
+}
+
+/// Sorts `frame_2.png` before `frame_10.png`.
+fn frame_number_key(path: &Path) -> (u64, String) {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    // last run of digits is the frame number
+    let number = file_name
+        .rsplit(|c: char| !c.is_ascii_digit())
+        .find(|digits| !digits.is_empty())
+        .and_then(|digits| digits.parse().ok())
+        .unwrap_or(0);
+
+    (number, file_name)
+}
+
+/// Frames of an animated GIF or APNG.
+pub struct AnimationSource {
+    path: PathBuf,
+    frames: Frames<'static>,
+    first: Option<DynamicImage>,
+    fps: Option<f64>,
+}
+
+impl AnimationSource {
+    pub fn new(path: &Path) -> Result<Self, String> {
+        let error =
+            |err: &dyn std::fmt::Display| format!("cannot open {}: {}", path.display(), err);
+
+        let reader = image::io::Reader::open(path)
+            .map_err(|err| error(&err))?
+            .with_guessed_format()
+            .map_err(|err| error(&err))?;
+
+        let format = reader.format();
+        let file = reader.into_inner();
+
+        let mut frames = match format {
+            Some(ImageFormat::Gif) => GifDecoder::new(file)
+                .map_err(|err| error(&err))?
+                .into_frames(),
+            Some(ImageFormat::Png) => {
+                let decoder = PngDecoder::new(file).map_err(|err| error(&err))?;
+
+                if !decoder.is_apng() {
+                    return Err(error(&"not an animated PNG, convert it as an image"));
+                }
+
+                decoder.apng().into_frames()
+            }
+            _ => return Err(error(&"only animated GIF and PNG are supported")),
+        };
+
+        // the first delay decides the frame rate
+        let (first, fps) = match frames.next() {
+            Some(frame) => {
+                let frame = frame.map_err(|err| error(&err))?;
+                let (numer, denom) = frame.delay().numer_denom_ms();
+                let delay_ms = numer as f64 / denom as f64;
+
+                (
+                    Some(DynamicImage::ImageRgba8(frame.into_buffer())),
+                    (delay_ms > 0.).then(|| 1000. / delay_ms),
+                )
+            }
+            None => (None, None),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            frames,
+            first,
+            fps,
+        })
+    }
+}
+
+impl FrameSource for AnimationSource {
+    fn next_frame(&mut self) -> Option<Result<DynamicImage, String>> {
+        if let Some(first) = self.first.take() {
+            return Some(Ok(first));
+        }
+
+        let frame = self
+            .frames
+            .next()?
+            .map_err(|err| format!("cannot decode a frame of {}: {}", self.path.display(), err));
+
+        Some(frame.map(|frame| DynamicImage::ImageRgba8(frame.into_buffer())))
+    }
+
+    fn fps(&self) -> Option<f64> {
+        self.fps
+    }
+}
+
+/// Cuts another source to a range, takes every nth frame and drops or repeats frames to hit an
+/// output frame rate.
+pub struct Resampled<S> {
+    inner: S,
+    start: usize,
+    end: Option<usize>,
+    stride: usize,
+    /// Output frames per strided input frame
+    ratio: f64,
+    output_fps: f64,
+    /// Whether the output frame rate tells more than the frametime it falls back to
+    fps_known: bool,
+    /// Index of the next frame `inner` gives
+    next_index: usize,
+    current: Option<(usize, DynamicImage)>,
+    output_index: usize,
+}
+
+impl<S: FrameSource> Resampled<S> {
+    /// Frame rates not given by `input` or `inner` follow `frametime`.
+    pub fn new(inner: S, input: &InputSettings, frametime: f64) -> Result<Self, String> {
+        let source_fps = input.fps.or(inner.fps());
+        let fps = source_fps.unwrap_or(1. / frametime);
+
+        let start = input.start.map_or(0, |start| start.to_frame_index(fps));
+        let end = input.end.map(|end| end.to_frame_index(fps));
+
+        if end.is_some_and(|end| end <= start) {
+            return Err(format!(
+                "end frame {} is not after start frame {}",
+                end.unwrap_or_default(),
+                start
+            ));
+        }
+
+        let strided_fps = fps / input.stride as f64;
+        let output_fps = input.output_fps.unwrap_or(strided_fps);
+
+        Ok(Self {
+            inner,
+            start,
+            end,
+            stride: input.stride,
+            ratio: strided_fps / output_fps,
+            output_fps,
+            fps_known: source_fps.is_some() || input.output_fps.is_some() || input.stride != 1,
+            next_index: 0,
+            current: None,
+            output_index: 0,
+        })
+    }
+}
+
+impl<S: FrameSource> FrameSource for Resampled<S> {
+    fn next_frame(&mut self) -> Option<Result<DynamicImage, String>> {
+        // nudged so exact ratios do not land just below a whole frame
+        let strided_index = (self.output_index as f64 * self.ratio + 1e-9).floor() as usize;
+        let wanted = self.start + strided_index * self.stride;
+
+        if self.end.is_some_and(|end| wanted >= end) {
+            return None;
+        }
+
+        while self.next_index <= wanted {
+            let frame = match self.inner.next_frame()? {
+                Ok(frame) => frame,
+                Err(err) => return Some(Err(err)),
+            };
+
+            self.current = Some((self.next_index, frame));
+            self.next_index += 1;
+        }
+
+        self.output_index += 1;
+
+        // repeats the frame when the output is faster than the input
+        self.current
+            .as_ref()
+            .filter(|(index, _)| *index == wanted)
+            .map(|(_, frame)| Ok(frame.clone()))
+    }
+
+    fn fps(&self) -> Option<f64> {
+        self.fps_known.then_some(self.output_fps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma};
+
+    use super::*;
+    use crate::settings::FramePosition;
+
+    /// `count` 1x1 frames whose pixel is their index.
+    struct Counting {
+        next: u8,
+        count: u8,
+        fps: Option<f64>,
+    }
+
+    impl FrameSource for Counting {
+        fn next_frame(&mut self) -> Option<Result<DynamicImage, String>> {
+            let index = self.next;
+            self.next += 1;
+
+            (index < self.count).then(|| {
+                Ok(DynamicImage::ImageLuma8(GrayImage::from_pixel(
+                    1,
+                    1,
+                    Luma([index]),
+                )))
+            })
+        }
+
+        fn fps(&self) -> Option<f64> {
+            self.fps
+        }
+    }
+
+    fn resampled(fps: Option<f64>, input: InputSettings) -> Resampled<Counting> {
+        let inner = Counting {
+            next: 0,
+            count: 10,
+            fps,
+        };
+
+        Resampled::new(inner, &input, 0.01).unwrap()
+    }
+
+    fn indices(source: &mut impl FrameSource) -> Vec<u8> {
+        std::iter::from_fn(|| source.next_frame())
+            .map(|frame| frame.unwrap().to_luma8().get_pixel(0, 0)[0])
+            .collect()
+    }
+
+    #[test]
+    fn drops_frames_for_a_lower_rate() {
+        let mut source = resampled(
+            Some(30.),
+            InputSettings {
+                output_fps: Some(12.),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(indices(&mut source), [0, 2, 5, 7]);
+        assert_eq!(source.fps(), Some(12.));
+
+        let mut source = resampled(
+            Some(30.),
+            InputSettings {
+                stride: 3,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(indices(&mut source), [0, 3, 6, 9]);
+        assert_eq!(source.fps(), Some(10.));
+    }
+
+    #[test]
+    fn repeats_frames_for_a_higher_rate() {
+        let mut source = resampled(
+            Some(20.),
+            InputSettings {
+                start: Some(FramePosition::Seconds(0.3)),
+                end: Some(FramePosition::Frame(8)),
+                output_fps: Some(50.),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(indices(&mut source), [6, 6, 6, 7, 7]);
+        assert_eq!(source.fps(), Some(50.));
+    }
+
+    #[test]
+    fn falls_back_to_the_frametime() {
+        let mut source = resampled(
+            None,
+            InputSettings {
+                start: Some(FramePosition::Seconds(0.05)),
+                ..Default::default()
+            },
+        );
+
+        // 100 fps from the 0.01 frametime, but the script keeps its own timing
+        assert_eq!(indices(&mut source), [5, 6, 7, 8, 9]);
+        assert_eq!(source.fps(), None);
+
+        let source = resampled(
+            None,
+            InputSettings {
+                stride: 2,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(source.fps(), Some(50.));
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_match("frame_*.png", "frame_12.png"));
+        assert!(wildcard_match("frame_*.png", "frame_.png"));
+        assert!(!wildcard_match("frame_*.png", "frame_12.jpg"));
+        assert!(!wildcard_match("frame_*.png", "f_12.png"));
+        assert!(wildcard_match("f?.png", "f1.png"));
+        assert!(!wildcard_match("f?.png", "f12.png"));
+        assert!(wildcard_match("a*b*c", "axxbyybc"));
+        assert!(!wildcard_match("a*b*c", "axxbyy"));
+        assert!(wildcard_match("*", ""));
+    }
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut paths: Vec<PathBuf> = names.iter().map(PathBuf::from).collect();
+        paths.sort_by_cached_key(|path| frame_number_key(path));
+
+        paths
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sorts_by_frame_number() {
+        assert_eq!(
+            sorted(&["frame_10.png", "frame_2.png", "frame_1.png"]),
+            ["frame_1.png", "frame_2.png", "frame_10.png"]
+        );
+        // the last number counts, leading zeros do not
+        assert_eq!(
+            sorted(&["take2_frame_010.png", "take2_frame_9.png"]),
+            ["take2_frame_9.png", "take2_frame_010.png"]
+        );
+    }
+
+    #[test]
+    fn sorts_by_name_without_a_number() {
+        assert_eq!(
+            sorted(&["b.png", "frame_1.png", "a.png", "frame_0.png"]),
+            ["a.png", "b.png", "frame_0.png", "frame_1.png"]
+        );
+    }
+
+    #[test]
+    fn replaces_the_frame_number() {
+        assert_eq!(numbered_pattern("frame_0012.png"), "frame_*.png");
+        assert_eq!(numbered_pattern("take2_frame_3.png"), "take2_frame_*.png");
+        assert_eq!(numbered_pattern("cover.png"), "cover.png");
+    }
+
+    #[test]
+    fn takes_one_sequence_from_a_folder() {
+        let folder = std::env::temp_dir().join(format!("sequence-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+
+        for name in ["f_2.png", "f_10.png", "notes.txt"] {
+            std::fs::write(folder.join(name), []).unwrap();
+        }
+        let single = SequenceSource::new(&folder).map(|source| source.paths.len());
+
+        std::fs::write(folder.join("frame_1.png"), []).unwrap();
+        let mixed = SequenceSource::new(&folder).map(|source| source.paths.len());
+        let pattern =
+            SequenceSource::new(&folder.join("frame_*.png")).map(|source| source.paths.len());
+
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(single, Ok(2));
+        assert!(mixed.unwrap_err().contains("f_*.png, frame_*.png"));
+        assert_eq!(pattern, Ok(1));
+    }
+}
@@ -0,0 +1,217 @@
+//! Keeps the script in time with the video.
+//!
+//! Every line takes time to play, so a fixed delay per frame slowly drifts away from the music.
+//! [`Timeline`] adds up what was really emitted and sizes each delay so frame N ends at N / fps.
+//!
+//! The engine also does not play frametimes as written: they are rounded to whole milliseconds
+//! and anything under half a millisecond is a 0ms frame, which bxt plays with `frametime0ms`.
+//! [`Quantization`] knows this, so the timeline adds up what the game plays and delays come out
+//! as whole milliseconds, alternating between neighbours to keep the average.
+
+use crate::{hltas::Line, order::angle_between, settings::TimingSettings};
+
+/// Seconds `lines` take to play as written.
+pub fn duration(lines: &[Line]) -> f64 {
+    Quantization::Exact.duration(lines)
+}
+
+/// How the engine turns a written frametime into the one it plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantization {
+    /// Played as written
+    Exact,
+    /// Rounded to whole milliseconds like GoldSrc
+    Milliseconds,
+}
+
+impl Quantization {
+    pub fn new(timing: &TimingSettings) -> Self {
+        if timing.quantize {
+            Self::Milliseconds
+        } else {
+            Self::Exact
+        }
+    }
+
+    /// Seconds the engine plays for `frametime`, 0 for a 0ms frame.
+    pub fn played(self, frametime: f64) -> f64 {
+        match self {
+            Self::Exact => frametime,
+            Self::Milliseconds => (frametime * 1000.).round() / 1000.,
+        }
+    }
+
+    /// What to write for a frame of `frametime`, so it plays exactly as long as it reads.
+    ///
+    /// Frames too short to survive the rounding become 0ms frames of `zero_ms_frametime`.
+    pub fn representable(self, frametime: f64, zero_ms_frametime: f64) -> f64 {
+        match self.played(frametime) {
+            played if played > 0. => played,
+            _ => zero_ms_frametime,
+        }
+    }
+
+    /// Seconds `lines` take to play in game.
+    pub fn duration(self, lines: &[Line]) -> f64 {
+        lines
+            .iter()
+            .map(|line| match line {
+                Line::FrameBulk(frame_bulk) => {
+                    self.played(frame_bulk.frametime) * frame_bulk.repeats as f64
+                }
+                _ => 0.,
+            })
+            .sum()
+    }
+
+    /// Settings in `timing` the engine cannot play as asked.
+    pub fn warnings(self, timing: &TimingSettings) -> Vec<String> {
+        if self == Self::Exact {
+            return vec![];
+        }
+
+        let mut res = vec![];
+
+        if self.played(timing.zero_ms_frametime) > 0. {
+            res.push(format!(
+                "zero ms frametime {} is played as {}ms, bxt only treats frames under 0.5ms as 0ms",
+                timing.zero_ms_frametime,
+                self.played(timing.zero_ms_frametime) * 1000.
+            ));
+        }
+
+        if timing.slow_draw && timing.slow_wait != self.played(timing.slow_wait) {
+            res.push(match self.played(timing.slow_wait) {
+                played if played > 0. => format!(
+                    "slow wait {}s is played as {}ms",
+                    timing.slow_wait,
+                    played * 1000.
+                ),
+                _ => format!(
+                    "slow wait {}s is under 0.5ms, it is played as a 0ms frame",
+                    timing.slow_wait
+                ),
+            });
+        }
+
+        let frametime = self.played(timing.frametime);
+
+        if timing.frametime != frametime {
+            res.push(if timing.drift_free {
+                format!(
+                    "frametime {}s is not whole milliseconds, delays alternate between {}ms and \
+                    {}ms to keep the average",
+                    timing.frametime,
+                    (timing.frametime * 1000.).floor(),
+                    (timing.frametime * 1000.).ceil()
+                )
+            } else if frametime > 0. {
+                format!(
+                    "frametime {}s is played as {}ms, drifting {:.3}ms every frame",
+                    timing.frametime,
+                    frametime * 1000.,
+                    (frametime - timing.frametime).abs() * 1000.
+                )
+            } else {
+                format!(
+                    "frametime {}s is under 0.5ms, delays are played as 0ms frames",
+                    timing.frametime
+                )
+            });
+        }
+
+        res
+    }
+}
+
+/// Where a conversion is in time, and where the view has been on the way.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    frame_duration: f64,
+    elapsed: f64,
+    frames: usize,
+    max_drift: f64,
+    view: Option<[f32; 2]>,
+    travel: f64,
+    collapsed: usize,
+}
+
+impl Timeline {
+    pub fn new(frame_duration: f64) -> Self {
+        Self {
+            frame_duration,
+            elapsed: 0.,
+            frames: 0,
+            max_drift: 0.,
+            view: None,
+            travel: 0.,
+            collapsed: 0,
+        }
+    }
+
+    pub fn advance(&mut self, seconds: f64) {
+        self.elapsed += seconds;
+    }
+
+    /// Seconds until the current frame should end, negative when it is already late.
+    pub fn remaining(&self) -> f64 {
+        (self.frames + 1) as f64 * self.frame_duration - self.elapsed
+    }
+
+    /// Marks the current frame as done and records how far off its end is.
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+
+        let drift = (self.elapsed - self.frames as f64 * self.frame_duration).abs();
+        self.max_drift = self.max_drift.max(drift);
+    }
+
+    /// Counts the current frame as a repeat of the one on screen.
+    pub fn collapse_frame(&mut self) {
+        self.collapsed += 1;
+    }
+
+    /// Follows the view through `lines`, adding up how far it turns.
+    pub fn follow_view(&mut self, lines: &[Line]) {
+        for line in lines {
+            let Line::FrameBulk(frame_bulk) = line else {
+                continue;
+            };
+
+            let previous = self.view.unwrap_or_default();
+            let view = [
+                frame_bulk.pitch.unwrap_or(previous[0]),
+                frame_bulk.yaw.unwrap_or(previous[1]),
+            ];
+
+            if self.view.is_some() {
+                self.travel += angle_between(previous, view);
+            }
+
+            self.view = Some(view);
+        }
+    }
+
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Largest distance in seconds between where a frame ended and where it should have.
+    pub fn max_drift(&self) -> f64 {
+        self.max_drift
+    }
+
+    /// Degrees the view turned while drawing.
+    pub fn travel(&self) -> f64 {
+        self.travel
+    }
+
+    /// Frames kept on screen instead of drawn.
+    pub fn collapsed(&self) -> usize {
+        self.collapsed
+    }
+}
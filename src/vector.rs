@@ -0,0 +1,441 @@
+//! Draws outlines as lines instead of one dot per pixel.
+//!
+//! Edge or outline pixels are chained into contours, contours are simplified into polylines
+//! with Ramer-Douglas-Peucker, and the view sweeps along every segment in even angle steps.
+
+use image::DynamicImage;
+
+use crate::{
+    order::angle_between,
+    projection::{dots_to_views, image_coordinate_to_viewangles},
+    render::{BiLevel, CannyEdge, Dots, Renderer},
+    settings::{ProjectionSettings, VectorSource},
+    Views,
+};
+
+/// Neighbours followed when tracing, straight ones first so contours do not cut corners
+const NEIGHBOURS: [[i64; 2]; 8] = [
+    [1, 0],
+    [0, 1],
+    [-1, 0],
+    [0, -1],
+    [1, 1],
+    [-1, 1],
+    [-1, -1],
+    [1, -1],
+];
+
+/// Farthest in pixels the end of a contour reaches over to another one, edges often have gaps
+const JOINT_RADIUS: i64 = 2;
+/// Points at the end of a contour it will not reach over to, they are the line itself
+const JOINT_SKIP: usize = 6;
+
+/// Traces outlines into strokes
+pub struct Vector {
+    pub source: VectorSource,
+    pub canny: CannyEdge,
+    /// Farthest in pixels a contour may stray from its simplified polyline
+    pub tolerance: f64,
+    /// Contours with fewer pixels are dropped
+    pub min_length: usize,
+}
+
+impl Renderer for Vector {
+    fn render(&self, img: DynamicImage) -> Dots {
+        let pixels = match self.source {
+            VectorSource::CannyEdge => self.canny.render(img),
+            VectorSource::BiLevel => outline(&BiLevel.render(img)),
+        };
+
+        let mut lines: Vec<Vec<[u32; 2]>> = vec![];
+
+        for contour in trace(&pixels) {
+            if contour.len() < self.min_length {
+                continue;
+            }
+
+            join(&mut lines, simplify(&contour, self.tolerance));
+        }
+
+        let mut res = Dots {
+            dimensions: pixels.dimensions,
+            points: vec![],
+            strokes: vec![],
+        };
+
+        for line in lines {
+            res.strokes.push(res.points.len());
+            res.points.extend(line);
+        }
+
+        res
+    }
+}
+
+/// Grid of `dots` for looking pixels up.
+#[derive(Clone)]
+struct Mask {
+    dimensions: (u32, u32),
+    cells: Vec<bool>,
+}
+
+impl Mask {
+    fn new(dots: &Dots) -> Self {
+        let (width, height) = dots.dimensions;
+        let mut cells = vec![false; width as usize * height as usize];
+
+        for &[x, y] in &dots.points {
+            cells[y as usize * width as usize + x as usize] = true;
+        }
+
+        Self {
+            dimensions: dots.dimensions,
+            cells,
+        }
+    }
+
+    /// Pixel `offset` away from `point`, `None` outside of the image.
+    fn neighbour(&self, [x, y]: [u32; 2], [dx, dy]: [i64; 2]) -> Option<[u32; 2]> {
+        let (x, y) = (x as i64 + dx, y as i64 + dy);
+        let (width, height) = (self.dimensions.0 as i64, self.dimensions.1 as i64);
+
+        ((0..width).contains(&x) && (0..height).contains(&y)).then_some([x as u32, y as u32])
+    }
+
+    fn get(&self, [x, y]: [u32; 2]) -> bool {
+        self.cells[y as usize * self.dimensions.0 as usize + x as usize]
+    }
+
+    /// Removes `point`, telling whether it was there.
+    fn take(&mut self, [x, y]: [u32; 2]) -> bool {
+        let cell = &mut self.cells[y as usize * self.dimensions.0 as usize + x as usize];
+        std::mem::replace(cell, false)
+    }
+
+    fn neighbours(&self, point: [u32; 2]) -> impl Iterator<Item = [u32; 2]> + '_ {
+        NEIGHBOURS
+            .iter()
+            .filter_map(move |&offset| self.neighbour(point, offset))
+            .filter(|&neighbour| self.get(neighbour))
+    }
+}
+
+/// Pixels of `dots` next to a pixel that is not, the image border counts as not.
+fn outline(dots: &Dots) -> Dots {
+    let mask = Mask::new(dots);
+
+    Dots {
+        dimensions: dots.dimensions,
+        points: dots
+            .points
+            .iter()
+            .copied()
+            .filter(|&point| {
+                NEIGHBOURS[..4].iter().any(|&offset| {
+                    mask.neighbour(point, offset)
+                        .is_none_or(|neighbour| !mask.get(neighbour))
+                })
+            })
+            .collect(),
+        strokes: vec![],
+    }
+}
+
+/// Chains neighbouring pixels of `dots` into contours.
+///
+/// Contours start from loose ends where there are any so lines are not split in the middle, and
+/// ends next to a pixel of an earlier contour, or of the start of their own, reach over to it so
+/// lines do not break up where contours meet.
+fn trace(dots: &Dots) -> Vec<Vec<[u32; 2]>> {
+    let mask = Mask::new(dots);
+    let mut left = mask.clone();
+    let mut res = vec![];
+
+    let loose_ends: Vec<[u32; 2]> = dots
+        .points
+        .iter()
+        .copied()
+        .filter(|&point| mask.neighbours(point).count() <= 1)
+        .collect();
+
+    for start in loose_ends.into_iter().chain(dots.points.iter().copied()) {
+        if !left.take(start) {
+            continue;
+        }
+
+        let mut contour = walk(&mut left, start);
+        contour.reverse();
+        contour.push(start);
+        contour.extend(walk(&mut left, start));
+
+        for _ in 0..2 {
+            if let Some(joint) = joint(&mask, &left, &contour) {
+                contour.push(joint);
+            }
+            contour.reverse();
+        }
+
+        res.push(contour);
+    }
+
+    res
+}
+
+/// Pixels left in `left` followed from `start` until there are none.
+fn walk(left: &mut Mask, start: [u32; 2]) -> Vec<[u32; 2]> {
+    let mut res = vec![];
+    let mut current = start;
+
+    loop {
+        let Some(next) = left.neighbours(current).next() else {
+            break;
+        };
+
+        left.take(next);
+        res.push(next);
+        current = next;
+    }
+
+    res
+}
+
+/// Closest pixel already traced around the end of `contour`, other than the few just before the
+/// end.
+fn joint(mask: &Mask, left: &Mask, contour: &[[u32; 2]]) -> Option<[u32; 2]> {
+    let end = *contour.last()?;
+    let recent = &contour[contour.len().saturating_sub(JOINT_SKIP)..];
+
+    (-JOINT_RADIUS..=JOINT_RADIUS)
+        .flat_map(|dx| (-JOINT_RADIUS..=JOINT_RADIUS).map(move |dy| [dx, dy]))
+        .filter_map(|offset| mask.neighbour(end, offset))
+        .filter(|&point| mask.get(point) && !left.get(point) && !recent.contains(&point))
+        .min_by_key(|&[x, y]| {
+            let (dx, dy) = (x.abs_diff(end[0]), y.abs_diff(end[1]));
+            dx * dx + dy * dy
+        })
+}
+
+/// Adds `line` to `lines`, carrying on the first one it shares an end with.
+///
+/// Gaps in the edges break outlines up into contours reaching over to each other, drawing them as
+/// one stroke saves starting every piece again on the point they share.
+fn join(lines: &mut Vec<Vec<[u32; 2]>>, mut line: Vec<[u32; 2]>) {
+    for other in lines.iter_mut() {
+        if other.last() == line.last() || other.first() == line.first() {
+            line.reverse();
+        }
+
+        if other.last() == line.first() {
+            other.extend_from_slice(&line[1..]);
+            return;
+        }
+
+        if other.first() == line.last() {
+            line.pop();
+            other.splice(0..0, line);
+            return;
+        }
+    }
+
+    lines.push(line);
+}
+
+/// Ramer-Douglas-Peucker, keeps the ends and every point farther than `tolerance` from the line
+/// through the points kept around it.
+fn simplify(points: &[[u32; 2]], tolerance: f64) -> Vec<[u32; 2]> {
+    if points.len() <= 2 {
+        return points.to_vec();
+    }
+
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let (idx, distance) = points[1..points.len() - 1]
+        .iter()
+        .map(|&point| distance_to_segment(point, first, last))
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .expect("more than two points");
+
+    if distance <= tolerance {
+        return vec![first, last];
+    }
+
+    let mut res = simplify(&points[..=idx + 1], tolerance);
+    res.pop();
+    res.extend(simplify(&points[idx + 1..], tolerance));
+    res
+}
+
+fn distance_to_segment([x, y]: [u32; 2], [ax, ay]: [u32; 2], [bx, by]: [u32; 2]) -> f64 {
+    let (x, y, ax, ay, bx, by) = (
+        x as f64, y as f64, ax as f64, ay as f64, bx as f64, by as f64,
+    );
+    let (dx, dy) = (bx - ax, by - ay);
+    let length = dx * dx + dy * dy;
+
+    // closed loops start and end on the same point
+    let t = if length == 0. {
+        0.
+    } else {
+        (((x - ax) * dx + (y - ay) * dy) / length).clamp(0., 1.)
+    };
+
+    (x - (ax + t * dx)).hypot(y - (ay + t * dy))
+}
+
+/// Views sweeping along every stroke of `views`, each starting at its index in `strokes`, in
+/// steps of at most `step` degrees of pitch and of yaw.
+pub fn sweep(views: &Views, strokes: &[usize], step: f64) -> Views {
+    let mut res = vec![];
+    let ends = strokes.iter().skip(1).copied().chain([views.len()]);
+
+    for (start, end) in strokes.iter().copied().zip(ends) {
+        let stroke = &views[start..end];
+        res.extend(stroke.first());
+
+        for pair in stroke.windows(2) {
+            let [from, to] = [pair[0], pair[1]];
+            let count = steps(from, to, step);
+            let pitch = to[0] as f64 - from[0] as f64;
+            // yaw wraps around
+            let yaw = (to[1] as f64 - from[1] as f64 + 180.).rem_euclid(360.) - 180.;
+
+            res.extend((1..=count).map(|i| {
+                let t = i as f64 / count as f64;
+                [
+                    (from[0] as f64 + pitch * t) as f32,
+                    (from[1] as f64 + yaw * t) as f32,
+                ]
+            }));
+        }
+    }
+
+    res
+}
+
+/// Steps [`sweep`] takes from `from` to `to`, like the pixels of a line every step moves at most
+/// `step` each way so diagonals take no more steps than straight lines.
+fn steps(from: [f32; 2], to: [f32; 2], step: f64) -> usize {
+    let pitch = to[0] as f64 - from[0] as f64;
+    let yaw = (to[1] as f64 - from[1] as f64 + 180.).rem_euclid(360.) - 180.;
+
+    (pitch.abs().max(yaw.abs()) / step).ceil().max(1.) as usize
+}
+
+/// Degrees [`sweep`] steps along the strokes of a frame of `dimensions`, `stroke_step` but never
+/// under one image pixel, smaller steps only draw the same pixels again.
+pub fn stroke_step(
+    dimensions: (u32, u32),
+    projection: &ProjectionSettings,
+    stroke_step: Option<f64>,
+) -> f64 {
+    let (x, y) = (dimensions.0 / 2, dimensions.1 / 2);
+    let center = image_coordinate_to_viewangles(dimensions, x, y, projection);
+    // pixels are the widest in the middle
+    let pixel = angle_between(
+        center,
+        image_coordinate_to_viewangles(dimensions, x + 1, y, projection),
+    )
+    .min(angle_between(
+        center,
+        image_coordinate_to_viewangles(dimensions, x, y + 1, projection),
+    ));
+
+    stroke_step.map_or(pixel, |step| step.max(pixel))
+}
+
+/// Views [`sweep`] draws every stroke of `dots` with once projected with `projection`.
+pub fn swept_lengths(
+    dots: &Dots,
+    projection: &ProjectionSettings,
+    stroke_step: Option<f64>,
+) -> Vec<usize> {
+    let views = dots_to_views(dots, projection);
+    let step = self::stroke_step(dots.dimensions, projection, stroke_step);
+    let ends = dots.strokes.iter().skip(1).copied().chain([views.len()]);
+
+    dots.strokes
+        .iter()
+        .copied()
+        .zip(ends)
+        .map(|(start, end)| {
+            let stroke = &views[start..end];
+            let swept: usize = stroke
+                .windows(2)
+                .map(|pair| steps(pair[0], pair[1], step))
+                .sum();
+
+            stroke.len().min(1) + swept
+        })
+        .collect()
+}
+
+/// Views drawing `dots`, strokes counted as [`sweep`] draws them.
+pub fn view_count(dots: &Dots, projection: &ProjectionSettings, stroke_step: Option<f64>) -> usize {
+    if dots.strokes.is_empty() {
+        return dots.points.len();
+    }
+
+    swept_lengths(dots, projection, stroke_step)
+        .into_iter()
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma};
+
+    use super::*;
+    use crate::{
+        settings::{Mode, Settings},
+        Pipeline,
+    };
+
+    /// White disc on black.
+    fn disc() -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(320, 180, |x, y| {
+            let (dx, dy) = (x as i64 - 160, y as i64 - 90);
+            Luma([if dx * dx + dy * dy < 60 * 60 { 255 } else { 0 }])
+        }))
+    }
+
+    fn line_count(mode: Mode) -> usize {
+        let settings = Settings {
+            mode,
+            scale_factor: 0.5,
+            ..Settings::default()
+        };
+
+        Pipeline::new(settings).process_frame(disc()).len()
+    }
+
+    #[test]
+    fn draws_fewer_lines_than_canny_edge() {
+        assert!(line_count(Mode::Vector) < line_count(Mode::CannyEdge));
+    }
+
+    #[test]
+    fn traces_an_outline_as_one_stroke() {
+        let vector = Vector {
+            source: VectorSource::BiLevel,
+            canny: CannyEdge {
+                sigma: 1.2,
+                strong_threshold: 0.2,
+                weak_threshold: 0.01,
+            },
+            tolerance: 1.,
+            min_length: 3,
+        };
+
+        assert_eq!(vector.render(disc()).strokes, [0]);
+    }
+
+    #[test]
+    fn steps_at_least_one_pixel() {
+        let projection = ProjectionSettings::default();
+        let pixel = stroke_step((80, 45), &projection, None);
+
+        assert!((pixel - 0.5).abs() < 1e-4);
+        assert_eq!(stroke_step((80, 45), &projection, Some(0.1)), pixel);
+        assert_eq!(stroke_step((80, 45), &projection, Some(2.)), 2.);
+    }
+}
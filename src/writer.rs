@@ -0,0 +1,170 @@
+//! Writes scripts in the background without losing them.
+//!
+//! Files are written by a small pool of threads that is joined before the conversion returns,
+//! and every file goes to a temporary name first and is renamed into place, so a script on disk
+//! is either complete or not there at all.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::hltas::Hltas;
+
+const WORKERS: usize = 2;
+/// Scripts waiting to be written before the pipeline blocks
+const QUEUE_SIZE: usize = 64;
+
+/// Writes `contents` to `path` through a temporary file next to it.
+pub fn write_atomic(path: &Path, contents: impl std::fmt::Display) -> Result<(), String> {
+    let res = AtomicFile::create(path).and_then(|mut file| {
+        write!(file, "{contents}")?;
+        file.commit()
+    });
+
+    res.map_err(|err| format!("cannot write {}: {}", path.display(), err))
+}
+
+/// File written under a temporary name and renamed into place by [`AtomicFile::commit`].
+///
+/// Dropping it without committing removes the temporary file.
+pub struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    file: Option<BufWriter<File>>,
+}
+
+impl AtomicFile {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+
+        Ok(Self {
+            file: Some(BufWriter::new(File::create(&temp_path)?)),
+            path: path.to_path_buf(),
+            temp_path,
+        })
+    }
+
+    /// Flushes everything to disk and moves the file to its real name.
+    pub fn commit(mut self) -> io::Result<()> {
+        let file = self.file.take().expect("only taken here");
+
+        let res = file
+            .into_inner()
+            .map_err(io::Error::from)
+            .and_then(|file| file.sync_all())
+            .and_then(|_| std::fs::rename(&self.temp_path, &self.path));
+
+        if res.is_err() {
+            std::fs::remove_file(&self.temp_path).ok();
+        }
+
+        res
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.as_mut().expect("only taken on commit").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_mut().expect("only taken on commit").flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            std::fs::remove_file(&self.temp_path).ok();
+        }
+    }
+}
+
+/// Pool of threads writing scripts, [`ScriptWriter::finish`] waits for all of them.
+pub struct ScriptWriter {
+    sender: SyncSender<(PathBuf, Hltas)>,
+    workers: Vec<JoinHandle<()>>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl ScriptWriter {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let error = Arc::new(Mutex::new(None));
+
+        let workers = (0..WORKERS)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let error = error.clone();
+                thread::spawn(move || work(&receiver, &error))
+            })
+            .collect();
+
+        Self {
+            sender,
+            workers,
+            error,
+        }
+    }
+
+    /// Queues `hltas` to be written to `path`, fails once any earlier write failed.
+    pub fn write(&self, path: PathBuf, hltas: Hltas) -> Result<(), String> {
+        if let Some(error) = self.error.lock().unwrap().clone() {
+            return Err(error);
+        }
+
+        self.sender
+            .send((path, hltas))
+            .map_err(|_| "script writers stopped".to_string())
+    }
+
+    /// Waits for every queued script, returning the first write error.
+    pub fn finish(self) -> Result<(), String> {
+        drop(self.sender);
+
+        for worker in self.workers {
+            worker
+                .join()
+                .map_err(|_| "script writer panicked".to_string())?;
+        }
+
+        match self.error.lock().unwrap().take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for ScriptWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn work(receiver: &Mutex<Receiver<(PathBuf, Hltas)>>, error: &Mutex<Option<String>>) {
+    loop {
+        // the lock is only held while waiting for the next script
+        let Ok((path, hltas)) = receiver.lock().unwrap().recv() else {
+            break;
+        };
+
+        // keep draining after a failure so the pipeline never blocks on a full queue
+        if error.lock().unwrap().is_some() {
+            continue;
+        }
+
+        if let Err(err) = write_atomic(&path, &hltas) {
+            error.lock().unwrap().get_or_insert(err);
+        }
+    }
+}